@@ -0,0 +1,165 @@
+//! In-memory LRU + TTL cache of Matomo API responses
+//!
+//! LLM sessions tend to ask the same question several times in a row; serving
+//! identical calls from memory for a short while spares the Matomo instance.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Cached response with its insertion time and recency stamp
+#[derive(Debug)]
+struct Entry {
+    value: serde_json::Value,
+    inserted: Instant,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    entries: HashMap<String, Entry>,
+    /// Monotonic counter used as the recency stamp
+    clock: u64,
+}
+
+/// Bounded cache evicting the least recently used entry when full
+#[derive(Debug)]
+pub struct ResponseCache {
+    capacity: usize,
+    ttl: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl ResponseCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Cache key of a call: method plus its parameters in sorted order
+    pub fn key(module: &str, action: &str, params: &HashMap<String, serde_json::Value>) -> String {
+        let sorted: BTreeMap<&String, &serde_json::Value> = params.iter().collect();
+        format!(
+            "{}.{}?{}",
+            module,
+            action,
+            serde_json::to_string(&sorted).unwrap_or_default()
+        )
+    }
+
+    /// Fresh cached value for `key`, expired entries are dropped
+    pub fn get(&self, key: &str) -> Option<serde_json::Value> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.clock += 1;
+        let clock = inner.clock;
+
+        match inner.entries.get_mut(key) {
+            Some(entry) if entry.inserted.elapsed() < self.ttl => {
+                entry.last_used = clock;
+                Some(entry.value.clone())
+            }
+            Some(_) => {
+                inner.entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Drop every entry, e.g. once the responses were fetched with a stale token
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.entries.clear();
+    }
+
+    pub fn insert(&self, key: String, value: serde_json::Value) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.clock += 1;
+        let clock = inner.clock;
+
+        if !inner.entries.contains_key(&key) && inner.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            inner
+                .entries
+                .retain(|_, entry| entry.inserted.elapsed() < ttl);
+            if inner.entries.len() >= self.capacity {
+                let oldest = inner
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(k, _)| k.clone());
+                if let Some(oldest) = oldest {
+                    inner.entries.remove(&oldest);
+                }
+            }
+        }
+
+        inner.entries.insert(
+            key,
+            Entry {
+                value,
+                inserted: Instant::now(),
+                last_used: clock,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_key_ignores_parameter_order() {
+        let a: HashMap<String, serde_json::Value> = [
+            ("idSite".to_string(), json!(1)),
+            ("period".to_string(), json!("day")),
+        ]
+        .into();
+        let b: HashMap<String, serde_json::Value> = [
+            ("period".to_string(), json!("day")),
+            ("idSite".to_string(), json!(1)),
+        ]
+        .into();
+        assert_eq!(
+            ResponseCache::key("VisitsSummary", "get", &a),
+            ResponseCache::key("VisitsSummary", "get", &b)
+        );
+    }
+
+    #[test]
+    fn test_least_recently_used_is_evicted() {
+        let cache = ResponseCache::new(2, Duration::from_secs(60));
+        cache.insert("a".to_string(), json!(1));
+        cache.insert("b".to_string(), json!(2));
+        assert_eq!(cache.get("a"), Some(json!(1)));
+        cache.insert("c".to_string(), json!(3));
+
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(json!(1)));
+        assert_eq!(cache.get("c"), Some(json!(3)));
+    }
+
+    #[test]
+    fn test_clear_drops_entries() {
+        let cache = ResponseCache::new(2, Duration::from_secs(60));
+        cache.insert("a".to_string(), json!(1));
+        cache.clear();
+        assert_eq!(cache.get("a"), None);
+    }
+
+    #[test]
+    fn test_expired_entries_are_not_served() {
+        let cache = ResponseCache::new(2, Duration::ZERO);
+        cache.insert("a".to_string(), json!(1));
+        assert_eq!(cache.get("a"), None);
+    }
+}
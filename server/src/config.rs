@@ -0,0 +1,166 @@
+//! Named Matomo instances read from a TOML file (`--config`)
+//!
+//! ```toml
+//! default = "production"
+//!
+//! [instances.production]
+//! url = "https://matomo.example.com"
+//! token = "..."
+//! site_id = "1"
+//!
+//! [instances.staging]
+//! url = "https://matomo.staging.example.com"
+//! token = "..."
+//! insecure = true
+//! ```
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Name of the instance `--url`/`--openapi` define next to `--config`
+pub const CLI_INSTANCE: &str = "default";
+
+/// One Matomo backend
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InstanceConfig {
+    pub url: String,
+    #[serde(default)]
+    pub token: Option<String>,
+    /// idSite used by tool calls on this instance that omit it
+    #[serde(default)]
+    pub site_id: Option<String>,
+    /// Skip TLS certificate validation for this instance
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Instance used when a tool call does not pick one (defaults to the first)
+    #[serde(default)]
+    pub default: Option<String>,
+    pub instances: BTreeMap<String, InstanceConfig>,
+}
+
+impl Config {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {:?}", path))?;
+        let config: Config = toml::from_str(&text)
+            .with_context(|| format!("Failed to parse config file {:?}", path))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.instances.is_empty() {
+            anyhow::bail!("The config file defines no [instances.<name>]");
+        }
+        if let Some(ref name) = self.default {
+            if !self.instances.contains_key(name) {
+                anyhow::bail!(
+                    "Default instance '{}' is not defined (instances: {})",
+                    name,
+                    self.names().join(", ")
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Name of the default instance
+    pub fn default_name(&self) -> &str {
+        match self.default {
+            Some(ref name) => name,
+            None => self.instances.keys().next().map_or("", String::as_str),
+        }
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.instances.keys().map(String::as_str).collect()
+    }
+
+    /// Reject an instance named like the one `--url`/`--openapi` define,
+    /// which would otherwise shadow it
+    pub fn check_cli_instance(&self) -> Result<()> {
+        if self.instances.contains_key(CLI_INSTANCE) {
+            anyhow::bail!(
+                "Instance '{}' of the config file clashes with the one --url/--openapi \
+                 define; rename it, or drop --url/--openapi to use it",
+                CLI_INSTANCE
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_instances() {
+        let config: Config = toml::from_str(
+            r#"
+            default = "staging"
+
+            [instances.production]
+            url = "https://matomo.example.com"
+            token = "abc"
+            site_id = "3"
+
+            [instances.staging]
+            url = "https://staging.example.com"
+            insecure = true
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(config.default_name(), "staging");
+        assert_eq!(config.names(), vec!["production", "staging"]);
+        assert_eq!(config.instances["production"].site_id.as_deref(), Some("3"));
+        assert!(config.instances["staging"].insecure);
+        assert!(config.instances["staging"].token.is_none());
+    }
+
+    #[test]
+    fn test_unknown_default_is_rejected() {
+        let config: Config = toml::from_str(
+            r#"
+            default = "missing"
+            [instances.production]
+            url = "https://matomo.example.com"
+            "#,
+        )
+        .unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("production"), "{}", err);
+    }
+
+    #[test]
+    fn test_cli_instance_name_clash_is_rejected() {
+        let config: Config = toml::from_str(
+            r#"
+            [instances.default]
+            url = "https://matomo.example.com"
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+        assert!(config.check_cli_instance().is_err());
+
+        let config: Config = toml::from_str(
+            r#"
+            [instances.production]
+            url = "https://matomo.example.com"
+            "#,
+        )
+        .unwrap();
+        config.check_cli_instance().unwrap();
+    }
+}
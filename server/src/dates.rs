@@ -0,0 +1,191 @@
+//! Checks of the `date` and `period` report parameters
+//!
+//! Models often write dates the way they read them ("2024/01/31") or use
+//! adjectives for periods ("daily"). Obvious mistakes are corrected, anything
+//! else is rejected with the formats Matomo accepts.
+
+/// Date keywords understood by Matomo
+const DATE_KEYWORDS: &[&str] = &[
+    "today",
+    "yesterday",
+    "yesterdaySameTime",
+    "now",
+    "lastWeek",
+    "lastMonth",
+    "lastYear",
+];
+
+/// Accepted `date` formats, quoted in error messages
+pub const DATE_FORMATS: &str = "YYYY-MM-DD, today, yesterday, lastN, previousN \
+                                or a range YYYY-MM-DD,YYYY-MM-DD";
+
+/// Canonical period for common synonyms ("daily" -> "day")
+pub fn period_synonym(period: &str) -> Option<&'static str> {
+    match period.trim().to_ascii_lowercase().as_str() {
+        "daily" | "days" => Some("day"),
+        "weekly" | "weeks" => Some("week"),
+        "monthly" | "months" => Some("month"),
+        "yearly" | "annual" | "annually" | "years" => Some("year"),
+        "ranges" | "custom" => Some("range"),
+        _ => None,
+    }
+}
+
+/// Validate a `date` value, returning its corrected spelling if it had to change
+pub fn normalize_date(date: &str) -> Result<String, String> {
+    let parts: Vec<&str> = date.split(',').map(str::trim).collect();
+    let normalized = match parts.as_slice() {
+        [single] => normalize_single(single, true),
+        [start, end] => match (normalize_single(start, false), normalize_single(end, false)) {
+            (Some(start), Some(end)) => Some(format!("{},{}", start, end)),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    normalized.ok_or_else(|| {
+        format!(
+            "Invalid date '{}'. Accepted formats: {}",
+            date, DATE_FORMATS
+        )
+    })
+}
+
+/// Whether a (normalized) date selects several periods, as `period=range` needs
+pub fn is_multi_date(date: &str) -> bool {
+    date.contains(',') || relative_count(date).is_some()
+}
+
+/// Check an explicit `start,end` range (normalized) used with `period=range`
+///
+/// Both ends must be calendar dates and the start must not come after the
+/// end. Keywords such as `today` cannot be compared offline and are let through.
+pub fn check_date_range(date: &str) -> Result<(), String> {
+    let Some((start, end)) = date.split_once(',') else {
+        return Ok(());
+    };
+    for day in [start, end] {
+        if let Some((year, month, day_of_month)) = ymd(day) {
+            if day_of_month > days_in_month(year, month) {
+                return Err(format!(
+                    "Invalid date range '{}': {} is not a calendar date",
+                    date, day
+                ));
+            }
+        }
+    }
+    if ymd(start).is_some() && ymd(end).is_some() && start > end {
+        return Err(format!(
+            "Invalid date range '{}': the start date comes after the end date, \
+             did you mean '{},{}'?",
+            date, end, start
+        ));
+    }
+    Ok(())
+}
+
+/// Year, month and day of a normalized YYYY-MM-DD date
+fn ymd(date: &str) -> Option<(u32, u32, u32)> {
+    let mut fields = date.splitn(3, '-').map(|field| field.parse::<u32>().ok());
+    Some((fields.next()??, fields.next()??, fields.next()??))
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Normalize one date; `lastN` / `previousN` are only valid on their own
+fn normalize_single(date: &str, allow_relative: bool) -> Option<String> {
+    if let Some(keyword) = DATE_KEYWORDS
+        .iter()
+        .find(|keyword| keyword.eq_ignore_ascii_case(date))
+    {
+        return Some(keyword.to_string());
+    }
+
+    if allow_relative {
+        if let Some((prefix, n)) = relative_count(date) {
+            return Some(format!("{}{}", prefix, n));
+        }
+    }
+
+    // YYYY-MM-DD, also written with '/' or '.' or without zero padding
+    let fields: Vec<&str> = date.split(['-', '/', '.']).collect();
+    let [year, month, day] = fields.as_slice() else {
+        return None;
+    };
+    let year: u32 = year.parse().ok().filter(|_| year.len() == 4)?;
+    let month: u32 = month.parse().ok().filter(|m| (1..=12).contains(m))?;
+    let day: u32 = day.parse().ok().filter(|d| (1..=31).contains(d))?;
+    Some(format!("{:04}-{:02}-{:02}", year, month, day))
+}
+
+/// Split `lastN` / `previousN` (case-insensitive, spaces allowed) into prefix and N
+fn relative_count(date: &str) -> Option<(&'static str, u32)> {
+    let lower = date.trim().to_ascii_lowercase();
+    ["last", "previous"].into_iter().find_map(|prefix| {
+        let n: u32 = lower.strip_prefix(prefix)?.trim().parse().ok()?;
+        (n > 0).then_some((prefix, n))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_date() {
+        assert_eq!(normalize_date("2024-01-31").unwrap(), "2024-01-31");
+        assert_eq!(normalize_date("2024/1/5").unwrap(), "2024-01-05");
+        assert_eq!(normalize_date("Yesterday").unwrap(), "yesterday");
+        assert_eq!(normalize_date("last 7").unwrap(), "last7");
+        assert_eq!(
+            normalize_date("2024/01/01, today").unwrap(),
+            "2024-01-01,today"
+        );
+
+        for invalid in ["2024-13-01", "01/31/2024", "last7,today", "tomorrow", ""] {
+            let err = normalize_date(invalid).unwrap_err();
+            assert!(err.contains("YYYY-MM-DD"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_period_synonyms_and_multi_dates() {
+        assert_eq!(period_synonym("Daily"), Some("day"));
+        assert_eq!(period_synonym("day"), None);
+        assert!(is_multi_date("last7"));
+        assert!(is_multi_date("2024-01-01,2024-01-31"));
+        assert!(!is_multi_date("2024-01-01"));
+    }
+
+    #[test]
+    fn test_check_date_range() {
+        for valid in [
+            "2024-01-01,2024-01-31",
+            "2024-02-29,2024-02-29",
+            "2024-01-01,today",
+            "last7",
+        ] {
+            assert!(check_date_range(valid).is_ok(), "{}", valid);
+        }
+
+        let err = check_date_range("2024-03-31,2024-03-01").unwrap_err();
+        assert!(
+            err.contains("did you mean '2024-03-01,2024-03-31'"),
+            "{}",
+            err
+        );
+
+        let err = check_date_range("2023-02-29,2023-03-31").unwrap_err();
+        assert!(err.contains("2023-02-29 is not a calendar date"), "{}", err);
+        assert!(check_date_range("2024-01-01,2024-04-31").is_err());
+    }
+}
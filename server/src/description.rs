@@ -0,0 +1,116 @@
+//! Cleanup of tool descriptions
+//!
+//! Matomo method documentation is often multi-paragraph HTML. These helpers
+//! turn it into compact plain text for the tool list.
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Suffix appended to truncated descriptions
+const ELLIPSIS: char = '…';
+
+/// Strip HTML tags, decode common entities and collapse whitespace
+pub fn strip_html(html: &str) -> String {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let tag = TAG.get_or_init(|| Regex::new(r"<[^>]*>").expect("valid tag regex"));
+
+    let text = tag.replace_all(html, " ");
+    let text = decode_entities(&text);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Decode named and numeric HTML entities
+fn decode_entities(text: &str) -> String {
+    static ENTITY: OnceLock<Regex> = OnceLock::new();
+    let entity = ENTITY
+        .get_or_init(|| Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);").expect("valid"));
+
+    entity
+        .replace_all(text, |caps: &regex::Captures| {
+            let name = &caps[1];
+            let decoded = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => name
+                    .strip_prefix("#x")
+                    .or_else(|| name.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| name.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            decoded
+                .map(|c| c.to_string())
+                .unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+/// Truncate to at most `max_chars` characters at a word boundary, appending "…"
+pub fn truncate_at_word(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    // Keep room for the ellipsis
+    let budget = max_chars.saturating_sub(1);
+    let cut: String = text.chars().take(budget).collect();
+    let cut = match cut.rfind(char::is_whitespace) {
+        Some(pos) if pos > 0 => &cut[..pos],
+        _ => cut.as_str(),
+    };
+
+    format!("{}{}", cut.trim_end_matches([' ', ',', ';', ':']), ELLIPSIS)
+}
+
+/// Strip HTML and truncate a description to `max_chars`
+pub fn compact_description(description: &str, max_chars: usize) -> String {
+    truncate_at_word(&strip_html(description), max_chars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_html_tags_and_entities() {
+        assert_eq!(
+            strip_html("<p>Visits &amp; actions</p>\n<br/><b>per&nbsp;day</b> &#233;t&#xE9;"),
+            "Visits & actions per day été"
+        );
+    }
+
+    #[test]
+    fn test_strip_html_keeps_unknown_entities() {
+        assert_eq!(strip_html("a &unknown; b"), "a &unknown; b");
+    }
+
+    #[test]
+    fn test_truncate_at_word_boundary() {
+        assert_eq!(
+            truncate_at_word("This report gives you an overview", 20),
+            "This report gives…"
+        );
+    }
+
+    #[test]
+    fn test_truncate_short_text_untouched() {
+        assert_eq!(truncate_at_word("Short", 20), "Short");
+    }
+
+    #[test]
+    fn test_truncate_single_long_word() {
+        assert_eq!(truncate_at_word("abcdefghij", 5), "abcd…");
+    }
+
+    #[test]
+    fn test_compact_description() {
+        assert_eq!(
+            compact_description("<p>First sentence.</p><p>Second paragraph here.</p>", 24),
+            "First sentence. Second…"
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! Classification of Matomo API errors
+//!
+//! Matomo reports every failure as `{"result": "error", "message": ...}`.
+//! The common classes are recognized from the message (or the HTTP status)
+//! so MCP clients get a stable code to react on instead of free text.
+
+use reqwest::StatusCode;
+use rmcp::model::ErrorCode;
+use rmcp::ErrorData;
+
+/// Known classes of Matomo errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatomoErrorKind {
+    /// Missing, invalid or expired token
+    AuthRequired,
+    /// The token lacks the access level the method needs
+    NoAccess,
+    /// The idSite does not exist
+    InvalidSite,
+    /// Transient: the report is being archived
+    Archiving,
+    /// Transient: Matomo is overloaded, under maintenance or hit a database deadlock
+    Busy,
+    /// Anything else
+    Other,
+}
+
+impl MatomoErrorKind {
+    /// Machine-readable code attached to the MCP error data
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::AuthRequired => "auth_required",
+            Self::NoAccess => "no_access",
+            Self::InvalidSite => "invalid_site",
+            Self::Archiving => "archiving_in_progress",
+            Self::Busy => "temporarily_unavailable",
+            Self::Other => "matomo_error",
+        }
+    }
+
+    /// Whether the same call may succeed if it is sent again a bit later
+    pub fn is_transient(self) -> bool {
+        matches!(self, Self::Archiving | Self::Busy)
+    }
+
+    /// JSON-RPC error code (server-defined range, invalid_params for bad ids)
+    fn error_code(self) -> ErrorCode {
+        match self {
+            Self::AuthRequired => ErrorCode(-32001),
+            Self::NoAccess => ErrorCode(-32003),
+            Self::InvalidSite => ErrorCode::INVALID_PARAMS,
+            Self::Archiving => ErrorCode(-32004),
+            Self::Busy => ErrorCode(-32005),
+            Self::Other => ErrorCode::INTERNAL_ERROR,
+        }
+    }
+}
+
+/// Error answered by the Matomo API
+#[derive(Debug, thiserror::Error)]
+#[error("Matomo API error{}: {message}", .status.map(|s| format!(" ({})", s)).unwrap_or_default())]
+pub struct MatomoError {
+    pub kind: MatomoErrorKind,
+    pub message: String,
+    /// HTTP status, for errors that are not a Matomo JSON error
+    pub status: Option<StatusCode>,
+}
+
+impl MatomoError {
+    /// Error from the `message` of a `result: error` response
+    pub fn from_message(message: &str) -> Self {
+        Self {
+            kind: classify(message),
+            message: message.to_string(),
+            status: None,
+        }
+    }
+
+    /// Error from a non-success HTTP status and its body
+    pub fn from_status(status: StatusCode, body: &str) -> Self {
+        let kind = match status {
+            StatusCode::UNAUTHORIZED => MatomoErrorKind::AuthRequired,
+            StatusCode::FORBIDDEN => MatomoErrorKind::NoAccess,
+            _ => classify(body),
+        };
+        Self {
+            kind,
+            message: body.to_string(),
+            status: Some(status),
+        }
+    }
+
+    /// MCP error carrying the code and the original Matomo message
+    pub fn to_error_data(&self) -> ErrorData {
+        let mut data = serde_json::json!({
+            "code": self.kind.code(),
+            "matomo_message": self.message,
+        });
+        if let Some(status) = self.status {
+            data["http_status"] = status.as_u16().into();
+        }
+        if let Some(id_site) = site_id(&self.message) {
+            data["idSite"] = id_site.into();
+        }
+        let message = match self.kind {
+            MatomoErrorKind::Archiving => format!(
+                "{}. Matomo is still computing this report (archiving); the data is not \
+                 available yet, call the tool again in a few minutes",
+                self
+            ),
+            MatomoErrorKind::Busy => format!(
+                "{}. Matomo is temporarily unavailable (overloaded, under maintenance or \
+                 a database conflict); call the tool again in a few minutes",
+                self
+            ),
+            _ => self.to_string(),
+        };
+        ErrorData::new(self.kind.error_code(), message, Some(data))
+    }
+}
+
+/// Error for a site the token cannot view, detected before calling Matomo
+///
+/// Carries the `no_access` code like the Matomo error would, plus the sites
+/// the token can view so the model can pick one of them.
+pub fn site_access_error(id_site: u64, accessible: &[u64]) -> ErrorData {
+    const LISTED: usize = 20;
+    let mut listed: Vec<String> = accessible.iter().take(LISTED).map(u64::to_string).collect();
+    if accessible.len() > LISTED {
+        listed.push(format!("... ({} sites)", accessible.len()));
+    }
+    let message = match accessible {
+        [] => format!(
+            "The token has no view access to idSite {}, nor to any other site",
+            id_site
+        ),
+        _ => format!(
+            "The token has no view access to idSite {}; accessible site ids: {}",
+            id_site,
+            listed.join(", ")
+        ),
+    };
+    let data = serde_json::json!({
+        "code": MatomoErrorKind::NoAccess.code(),
+        "idSite": id_site,
+        "accessible_sites": accessible,
+    });
+    ErrorData::new(MatomoErrorKind::NoAccess.error_code(), message, Some(data))
+}
+
+/// Recognize the error class from Matomo's (English) message
+fn classify(message: &str) -> MatomoErrorKind {
+    let message = message.to_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|needle| message.contains(needle));
+
+    if has(&[
+        "token_auth",
+        "authenticat",
+        "log in",
+        "login",
+        "session has expired",
+    ]) {
+        MatomoErrorKind::AuthRequired
+    } else if has(&["website id", "idsite", "unexpected website"])
+        && has(&[
+            "not valid",
+            "invalid",
+            "unexpected website",
+            "does not exist",
+            "doesn't exist",
+            "not found",
+        ])
+    {
+        MatomoErrorKind::InvalidSite
+    } else if has(&[
+        "can't access",
+        "cannot access",
+        "requires",
+        "permission",
+        "access denied",
+    ]) && has(&["access", "permission"])
+    {
+        MatomoErrorKind::NoAccess
+    } else if has(&["currently being processed", "is being processed"])
+        || (has(&["archiving", "archive"])
+            && has(&[
+                "in progress",
+                "currently running",
+                "already running",
+                "please wait",
+                "try again",
+            ]))
+    {
+        MatomoErrorKind::Archiving
+    } else if has(&[
+        "try again later",
+        "please try again",
+        "try restarting transaction",
+        "deadlock",
+        "under maintenance",
+        "maintenance mode",
+    ]) {
+        MatomoErrorKind::Busy
+    } else {
+        MatomoErrorKind::Other
+    }
+}
+
+/// Site id mentioned in a message such as "website id = 3" or "idSite '3'"
+fn site_id(message: &str) -> Option<u64> {
+    let lower = message.to_lowercase();
+    let start = ["website id", "idsite"]
+        .iter()
+        .find_map(|needle| lower.find(needle).map(|i| i + needle.len()))?;
+    lower[start..]
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .split(|c: char| !c.is_ascii_digit())
+        .next()
+        .and_then(|digits| digits.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_common_messages() {
+        let cases = [
+            (
+                "Unable to authenticate with the provided token. It is either invalid or expired.",
+                MatomoErrorKind::AuthRequired,
+            ),
+            (
+                "You can't access this resource as it requires 'view' access for the website id = 1.",
+                MatomoErrorKind::NoAccess,
+            ),
+            (
+                "An unexpected website was found in the request: website id was set to '999' .",
+                MatomoErrorKind::InvalidSite,
+            ),
+            ("The period 'daily' is not supported.", MatomoErrorKind::Other),
+            (
+                "Archiving of this report is in progress, please try again in a few minutes.",
+                MatomoErrorKind::Archiving,
+            ),
+            (
+                "The report is currently being processed.",
+                MatomoErrorKind::Archiving,
+            ),
+            (
+                "Matomo is currently under maintenance. Please try again later.",
+                MatomoErrorKind::Busy,
+            ),
+            (
+                "SQLSTATE[40001]: Serialization failure: 1213 Deadlock found when trying to get \
+                 lock; try restarting transaction",
+                MatomoErrorKind::Busy,
+            ),
+            (
+                "Archiving was disabled for this website.",
+                MatomoErrorKind::Other,
+            ),
+        ];
+        for (message, kind) in cases {
+            assert_eq!(MatomoError::from_message(message).kind, kind, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_error_data_carries_code_and_site() {
+        let error = MatomoError::from_message(
+            "An unexpected website was found in the request: website id was set to '999' .",
+        );
+        let data = error.to_error_data();
+        assert_eq!(data.code, ErrorCode::INVALID_PARAMS);
+        let data = data.data.unwrap();
+        assert_eq!(data["code"], "invalid_site");
+        assert_eq!(data["idSite"], 999);
+    }
+
+    #[test]
+    fn test_transient_errors_tell_to_retry_later() {
+        let error = MatomoError::from_message("The report is currently being processed.");
+        assert!(error.kind.is_transient());
+        let data = error.to_error_data();
+        assert!(data.message.contains("archiving"), "{}", data.message);
+        assert_eq!(data.data.unwrap()["code"], "archiving_in_progress");
+
+        let error =
+            MatomoError::from_status(StatusCode::SERVICE_UNAVAILABLE, "Please try again later");
+        assert!(error.kind.is_transient());
+        let data = error.to_error_data();
+        assert!(
+            data.message.contains("in a few minutes"),
+            "{}",
+            data.message
+        );
+        assert!(!data.message.contains("archiving"), "{}", data.message);
+        assert_eq!(data.data.unwrap()["code"], "temporarily_unavailable");
+    }
+
+    #[test]
+    fn test_http_status_is_classified() {
+        let error = MatomoError::from_status(StatusCode::UNAUTHORIZED, "");
+        assert_eq!(error.kind, MatomoErrorKind::AuthRequired);
+        assert_eq!(error.to_string(), "Matomo API error (401 Unauthorized): ");
+    }
+
+    #[test]
+    fn test_site_access_error_lists_accessible_sites() {
+        let data = site_access_error(9, &[1, 2]);
+        assert_eq!(data.code, ErrorCode(-32003));
+        assert!(
+            data.message.ends_with("accessible site ids: 1, 2"),
+            "{}",
+            data.message
+        );
+        let data = data.data.unwrap();
+        assert_eq!(data["code"], "no_access");
+        assert_eq!(data["idSite"], 9);
+
+        let many: Vec<u64> = (1..=30).collect();
+        assert!(site_access_error(99, &many)
+            .message
+            .ends_with("... (30 sites)"));
+    }
+}
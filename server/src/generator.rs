@@ -6,26 +6,35 @@
 use anyhow::{Context, Result};
 use indexmap::IndexMap;
 use reqwest::Client;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 use tracing::{info, warn};
 use url::Url;
 
-use crate::http_client::build_client;
+use crate::http_client::{build_client, AuthMode, HttpConfig};
+use crate::matomo_client::extract_scalar;
 use crate::openapi::{
     Components, Info, OpenApiSpec, Operation, Parameter, ParameterSchema, PathItem, Response,
-    SecurityScheme, Server, Tag,
+    SecurityScheme, Server, Tag, COMPONENT_SCHEMAS_REF,
 };
 use crate::parser::{
-    convert_parameter, get_common_parameters, parse_api_reference, parse_method_list,
+    convert_parameter, get_common_parameters, get_hierarchical_parameters, infer_array_item_type,
+    methods_from_api_reference, parse_api_reference, parse_method_list, ParsedReportMethod,
 };
-use crate::types::{JsonSchema, MatomoMethod, MatomoParameter};
-use reqwest::header::HeaderMap;
+use crate::types::{JsonSchema, MatomoMethod, MatomoParameter, MethodMetadata};
 
 /// Configuration for OpenAPI generation
 pub struct GeneratorConfig {
     pub base_url: String,
     pub token: Option<String>,
     pub site_id: String,
+    pub auth_mode: AuthMode,
+    /// File caching the `listAllAPI` reference HTML between runs
+    pub reference_cache: Option<PathBuf>,
+    /// Refetch the reference even if the cache file exists
+    pub refresh_reference_cache: bool,
+    /// Methods kept per module, the best documented first
+    pub max_methods_per_module: Option<usize>,
 }
 
 impl GeneratorConfig {
@@ -34,6 +43,10 @@ impl GeneratorConfig {
             base_url,
             token,
             site_id: "1".to_string(),
+            auth_mode: AuthMode::default(),
+            reference_cache: None,
+            refresh_reference_cache: false,
+            max_methods_per_module: None,
         }
     }
 
@@ -41,27 +54,52 @@ impl GeneratorConfig {
         self.site_id = site_id;
         self
     }
+
+    pub fn with_auth_mode(mut self, auth_mode: AuthMode) -> Self {
+        self.auth_mode = auth_mode;
+        self
+    }
+
+    pub fn with_reference_cache(mut self, path: PathBuf, refresh: bool) -> Self {
+        self.reference_cache = Some(path);
+        self.refresh_reference_cache = refresh;
+        self
+    }
+
+    pub fn with_max_methods_per_module(mut self, max: usize) -> Self {
+        self.max_methods_per_module = Some(max);
+        self
+    }
 }
 
 /// Async Matomo client for introspection
 struct IntrospectionClient {
     client: Client,
-    base_url: Url,
+    endpoint: Url,
     token_auth: Option<String>,
+    auth_mode: AuthMode,
+    /// `--extra-param` parameters, sent with every request
+    extra_params: Vec<(String, String)>,
 }
 
 impl IntrospectionClient {
-    fn new(base_url: &str, token: Option<String>, extra_headers: &HeaderMap) -> Result<Self> {
+    fn new(
+        base_url: &str,
+        token: Option<String>,
+        auth_mode: AuthMode,
+        http: &HttpConfig,
+    ) -> Result<Self> {
         let base_url = Url::parse(base_url).context("Invalid base URL")?;
 
         // Use shared HTTP client with custom User-Agent and extra headers
-        // accept_invalid_certs=true for Matomo instances with self-signed certs
-        let client = build_client(true, extra_headers)?;
+        let client = build_client(http)?;
 
         Ok(Self {
             client,
-            base_url,
+            endpoint: http.api_endpoint(&base_url),
             token_auth: token,
+            auth_mode,
+            extra_params: http.extra_params.clone(),
         })
     }
 
@@ -72,28 +110,41 @@ impl IntrospectionClient {
         action: &str,
         extra_params: &[(&str, &str)],
     ) -> Result<String> {
-        let mut url = self.base_url.clone();
-        url.set_path("index.php");
+        let mut url = self.endpoint.clone();
 
         let method_str = format!("{}.{}", module, action);
 
+        // Configured parameters never override the ones of the request
+        let configured = self
+            .extra_params
+            .iter()
+            .filter(|(key, _)| !extra_params.iter().any(|(name, _)| name == key))
+            .map(|(key, value)| (key.as_str(), value.as_str()));
+        let extra_params: Vec<(&str, &str)> =
+            extra_params.iter().copied().chain(configured).collect();
+
         if let Some(ref token) = self.token_auth {
             // Use POST with form data when token is present
             let mut form_params: Vec<(&str, &str)> = vec![
                 ("module", "API"),
                 ("method", &method_str),
                 ("format", "JSON"),
-                ("token_auth", token),
             ];
 
+            if self.auth_mode == AuthMode::TokenAuthForm {
+                form_params.push(("token_auth", token));
+            }
+
             for (key, value) in extra_params {
                 form_params.push((key, value));
             }
 
-            let response = self
-                .client
-                .post(url.as_str())
-                .form(&form_params)
+            let mut request = self.client.post(url.as_str()).form(&form_params);
+            if self.auth_mode == AuthMode::BearerHeader {
+                request = request.bearer_auth(token);
+            }
+
+            let response = request
                 .send()
                 .await
                 .context("Failed to send POST request")?;
@@ -150,11 +201,7 @@ impl IntrospectionClient {
         let text = self.api_request("API", "getMatomoVersion", &[]).await?;
         let json: serde_json::Value =
             serde_json::from_str(&text).context("Failed to parse version JSON")?;
-        Ok(json
-            .get("value")
-            .and_then(|v| v.as_str())
-            .unwrap_or("unknown")
-            .to_string())
+        Ok(extract_scalar(&json).unwrap_or_else(|| "unknown".to_string()))
     }
 
     /// Fetch method list using getReportMetadata
@@ -167,6 +214,9 @@ impl IntrospectionClient {
     }
 
     /// Fetch API reference HTML
+    ///
+    /// Decoded lossily: invalid byte sequences become replacement characters,
+    /// which `parse_api_reference` drops.
     async fn fetch_api_reference(&self) -> Result<String> {
         self.api_request("API", "listAllAPI", &[]).await
     }
@@ -175,12 +225,17 @@ impl IntrospectionClient {
 /// Generate OpenAPI specification by introspecting a Matomo instance
 pub async fn generate_openapi_spec(
     config: &GeneratorConfig,
-    extra_headers: &HeaderMap,
+    http: &HttpConfig,
 ) -> Result<OpenApiSpec> {
     info!("Generating OpenAPI specification from Matomo instance...");
     info!("Target URL: {}", config.base_url);
 
-    let client = IntrospectionClient::new(&config.base_url, config.token.clone(), extra_headers)?;
+    let client = IntrospectionClient::new(
+        &config.base_url,
+        config.token.clone(),
+        config.auth_mode,
+        http,
+    )?;
 
     // Fetch Matomo version
     let version = client.fetch_version().await.unwrap_or_else(|e| {
@@ -192,26 +247,58 @@ pub async fn generate_openapi_spec(
     // Fetch method list
     info!("Fetching API method list for site {}...", config.site_id);
     let method_list_json = client.fetch_method_list(&config.site_id).await?;
-    let parsed_methods = parse_method_list(&method_list_json)?;
+    let mut parsed_methods = parse_method_list(&method_list_json)?;
     info!("Found {} API methods", parsed_methods.len());
 
     // Fetch API reference for parameter info
-    info!("Fetching API reference documentation...");
-    let api_reference = client.fetch_api_reference().await.unwrap_or_default();
-    let method_metadata = parse_api_reference(&api_reference).unwrap_or_default();
+    let api_reference = load_api_reference(&client, config).await;
+    let method_metadata = parse_api_reference(&api_reference).unwrap_or_else(|e| {
+        warn!("Could not parse the API reference: {:#}", e);
+        HashMap::new()
+    });
+
+    // Tokens without view access on the site get no report metadata at all
+    if parsed_methods.is_empty() {
+        parsed_methods = methods_from_api_reference(&method_metadata);
+        warn!(
+            "getReportMetadata returned no reports for site {} (does the token have view \
+             access?); using {} read methods from listAllAPI, tool descriptions and \
+             parameters will be sparse",
+            config.site_id,
+            parsed_methods.len()
+        );
+    }
+
+    if let Some(max) = config.max_methods_per_module {
+        parsed_methods = cap_methods_per_module(parsed_methods, max, &method_metadata);
+    }
 
     // Build complete method definitions
     let common_params = get_common_parameters();
+    let hierarchical_params = get_hierarchical_parameters();
     let mut matomo_methods: Vec<MatomoMethod> = Vec::new();
 
     for parsed_method in &parsed_methods {
         let method_name = format!("{}.{}", parsed_method.module, parsed_method.action);
 
-        // Get parameters from metadata if available
-        let mut parameters: Vec<MatomoParameter> = method_metadata
-            .get(&method_name)
-            .map(|m| m.parameters.iter().map(convert_parameter).collect())
-            .unwrap_or_default();
+        // Prefer structured parameters from the report metadata
+        let mut parameters: Vec<MatomoParameter> = parsed_method
+            .parameters
+            .iter()
+            .map(convert_parameter)
+            .collect();
+
+        let mut deprecated = parsed_method.deprecated;
+
+        // Fall back to the parameters scraped from the API reference
+        if let Some(metadata) = method_metadata.get(&method_name) {
+            deprecated |= metadata.deprecated;
+            for param in &metadata.parameters {
+                if !parameters.iter().any(|p| p.name == param.name) {
+                    parameters.push(convert_parameter(param));
+                }
+            }
+        }
 
         // Add common parameters if not already present
         for common_param in &common_params {
@@ -220,22 +307,49 @@ pub async fn generate_openapi_spec(
             }
         }
 
+        add_hierarchical_parameters(
+            &mut parameters,
+            &hierarchical_params,
+            parsed_method.has_subtables,
+        );
+
+        // Constrain the format parameter when the metadata lists supported formats
+        if let Some(ref formats) = parsed_method.supported_formats {
+            restrict_formats(&mut parameters, formats);
+        }
+
         matomo_methods.push(MatomoMethod {
             name: method_name,
             module: parsed_method.module.clone(),
             action: parsed_method.action.clone(),
             parameters,
             example_response: None,
-            response_schema: None,
+            response_schema: metric_response_schema(
+                &parsed_method.metric_units,
+                parsed_method.has_dimension,
+            )
+            .map(|mut schema| {
+                document_metrics(&mut schema, &parsed_method.metrics_documentation);
+                schema
+            }),
             description: parsed_method.documentation.clone(),
             category: parsed_method.category.clone(),
+            order: parsed_method.order,
+            metric_units: parsed_method.metric_units.clone(),
+            metrics: parsed_method.metrics.clone(),
+            deprecated,
         });
     }
 
     info!("Processed {} methods", matomo_methods.len());
 
     // Generate OpenAPI specification
-    let spec = build_openapi_spec(&matomo_methods, &config.base_url, &version);
+    let spec = build_openapi_spec(
+        &matomo_methods,
+        &config.base_url,
+        &version,
+        config.auth_mode,
+    );
 
     info!(
         "Generated OpenAPI spec with {} paths across {} modules",
@@ -246,30 +360,278 @@ pub async fn generate_openapi_spec(
     Ok(spec)
 }
 
+/// Fetch the API reference HTML, going through the on-disk cache if configured
+///
+/// The reference only feeds best-effort parameter parsing, so a stale cache
+/// is acceptable; it is reused until missing or `refresh_reference_cache` is
+/// set. A failed refetch falls back to the cached copy.
+async fn load_api_reference(client: &IntrospectionClient, config: &GeneratorConfig) -> String {
+    let cached = config
+        .reference_cache
+        .as_ref()
+        .and_then(|path| std::fs::read(path).ok())
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .filter(|html| !html.trim().is_empty());
+
+    if let (Some(html), false) = (&cached, config.refresh_reference_cache) {
+        info!(
+            "Using cached API reference from {:?}",
+            config.reference_cache.as_ref().unwrap_or(&PathBuf::new())
+        );
+        return html.clone();
+    }
+
+    info!("Fetching API reference documentation...");
+    match client.fetch_api_reference().await {
+        Ok(html) => {
+            if let Some(ref path) = config.reference_cache {
+                if let Err(e) = std::fs::write(path, &html) {
+                    warn!("Could not write API reference cache {:?}: {}", path, e);
+                }
+            }
+            html
+        }
+        Err(e) => {
+            warn!("Could not fetch API reference: {:#}", e);
+            cached.unwrap_or_default()
+        }
+    }
+}
+
+/// Build a response schema documenting the unit of each metric
+///
+/// Reports with a dimension return one row per dimension value, overview
+/// reports return a single row. Returns `None` when no unit is known.
+fn metric_response_schema(
+    metric_units: &BTreeMap<String, String>,
+    has_dimension: bool,
+) -> Option<JsonSchema> {
+    if metric_units.is_empty() {
+        return None;
+    }
+
+    let properties = metric_units
+        .iter()
+        .map(|(metric, unit)| {
+            let description = Some(format!("Unit: {}", unit));
+            // Percentages are strings such as "42%", numbers with format_metrics=0
+            let schema = if unit == "percentage" {
+                let variant = |schema_type: &str| JsonSchema {
+                    schema_type: schema_type.to_string(),
+                    ..Default::default()
+                };
+                JsonSchema {
+                    any_of: Some(vec![variant("number"), variant("string")]),
+                    description,
+                    ..Default::default()
+                }
+            } else {
+                JsonSchema {
+                    schema_type: "number".to_string(),
+                    description,
+                    ..Default::default()
+                }
+            };
+            (metric.clone(), schema)
+        })
+        .collect();
+
+    let row = JsonSchema {
+        schema_type: "object".to_string(),
+        properties: Some(properties),
+        ..Default::default()
+    };
+
+    Some(if has_dimension {
+        JsonSchema {
+            schema_type: "array".to_string(),
+            items: Some(Box::new(row)),
+            ..Default::default()
+        }
+    } else {
+        row
+    })
+}
+
+/// Append Matomo's explanation of each metric to its property description
+fn document_metrics(schema: &mut JsonSchema, documentation: &BTreeMap<String, String>) {
+    let row = match schema.items.as_mut() {
+        Some(items) => items.as_mut(),
+        None => schema,
+    };
+    for (metric, property) in row.properties.iter_mut().flatten() {
+        if let Some(doc) = documentation.get(metric).filter(|d| !d.is_empty()) {
+            property.description = Some(match property.description.take() {
+                Some(unit) => format!("{}. {}", unit, doc),
+                None => doc.clone(),
+            });
+        }
+    }
+}
+
+/// Tool description: the method documentation plus the metrics it returns
+fn operation_description(method: &MatomoMethod) -> Option<String> {
+    if method.metrics.is_empty() {
+        return method.description.clone();
+    }
+
+    let metrics: Vec<String> = method
+        .metrics
+        .iter()
+        .map(|(id, label)| {
+            if label.is_empty() || label == id {
+                id.clone()
+            } else {
+                format!("{} ({})", id, label)
+            }
+        })
+        .collect();
+    let line = format!("Returns metrics: {}", metrics.join(", "));
+
+    Some(match method.description {
+        Some(ref description) if !description.trim().is_empty() => {
+            format!("{}\n\n{}", description.trim_end(), line)
+        }
+        _ => line,
+    })
+}
+
+/// Document `flat` / `expanded`, adding them to hierarchical reports
+///
+/// The API reference lists them without a description for some methods;
+/// they are only added to reports whose rows have subtables.
+fn add_hierarchical_parameters(
+    parameters: &mut Vec<MatomoParameter>,
+    hierarchical_params: &[MatomoParameter],
+    has_subtables: bool,
+) {
+    for hierarchical in hierarchical_params {
+        match parameters.iter_mut().find(|p| p.name == hierarchical.name) {
+            Some(existing) if existing.description.is_none() => {
+                existing.description = hierarchical.description.clone();
+            }
+            Some(_) => {}
+            None if has_subtables => parameters.push(hierarchical.clone()),
+            None => {}
+        }
+    }
+}
+
+/// Restrict the `format` parameter to the formats a method supports
+fn restrict_formats(parameters: &mut [MatomoParameter], formats: &[String]) {
+    if let Some(format) = parameters.iter_mut().find(|p| p.name == "format") {
+        if !formats
+            .iter()
+            .any(|f| Some(f) == format.default_value.as_ref())
+        {
+            format.default_value = formats.first().cloned();
+        }
+        format.enum_values = Some(formats.to_vec());
+    }
+}
+
+/// Keep at most `max` methods of each module
+///
+/// Modules such as CustomDimensions expose many near-identical methods.
+/// Documented methods, then non-deprecated ones are kept; the kept methods
+/// stay in their original order.
+fn cap_methods_per_module(
+    methods: Vec<ParsedReportMethod>,
+    max: usize,
+    metadata: &HashMap<String, MethodMetadata>,
+) -> Vec<ParsedReportMethod> {
+    let score = |method: &ParsedReportMethod| {
+        let reference = metadata.get(&format!("{}.{}", method.module, method.action));
+        (
+            method.documentation.is_some(),
+            !method.deprecated && !reference.is_some_and(|r| r.deprecated),
+        )
+    };
+
+    let mut by_module: IndexMap<&str, Vec<usize>> = IndexMap::new();
+    for (index, method) in methods.iter().enumerate() {
+        by_module.entry(&method.module).or_default().push(index);
+    }
+
+    let mut kept = vec![true; methods.len()];
+    for (module, mut indices) in by_module {
+        if indices.len() <= max {
+            continue;
+        }
+        // Stable: equally scored methods keep their original precedence
+        indices.sort_by_key(|&i| std::cmp::Reverse(score(&methods[i])));
+        let dropped: Vec<&str> = indices[max..]
+            .iter()
+            .map(|&i| {
+                kept[i] = false;
+                methods[i].action.as_str()
+            })
+            .collect();
+        info!(
+            "Dropping {} of {} {} methods (--max-methods-per-module {}): {}",
+            dropped.len(),
+            dropped.len() + max,
+            module,
+            max,
+            dropped.join(", ")
+        );
+    }
+
+    methods
+        .into_iter()
+        .zip(kept)
+        .filter_map(|(method, kept)| kept.then_some(method))
+        .collect()
+}
+
 /// Build OpenAPI specification from Matomo methods
-fn build_openapi_spec(methods: &[MatomoMethod], base_url: &str, version: &str) -> OpenApiSpec {
+fn build_openapi_spec(
+    methods: &[MatomoMethod],
+    base_url: &str,
+    version: &str,
+    auth_mode: AuthMode,
+) -> OpenApiSpec {
     let mut paths: IndexMap<String, PathItem> = IndexMap::new();
-    let mut tags_set: HashMap<String, Tag> = HashMap::new();
+    // Module -> categories of its methods
+    let mut tags_set: IndexMap<String, Vec<&str>> = IndexMap::new();
+
+    // Group methods by report category, then by Matomo's display order, so
+    // clients rendering tools in list order show related reports together.
+    // Ties are broken by name: the order never depends on Matomo's response
+    let mut methods: Vec<&MatomoMethod> = methods.iter().collect();
+    methods.sort_by_key(|method| {
+        (
+            method.category.is_none(),
+            &method.category,
+            method.order.unwrap_or(i64::MAX),
+            &method.module,
+            &method.action,
+        )
+    });
 
     for method in methods {
         let operation = create_operation(method);
 
         // Add tag for this module
-        if !tags_set.contains_key(&method.module) {
-            tags_set.insert(
-                method.module.clone(),
-                Tag {
-                    name: method.module.clone(),
-                    description: Some(format!("{} module API methods", method.module)),
-                },
-            );
+        let categories = tags_set.entry(method.module.clone()).or_default();
+        if let Some(category) = method.category.as_deref() {
+            if !categories.contains(&category) {
+                categories.push(category);
+            }
         }
 
-        // Add operation to path
-        let method_path = format!(
-            "/index.php?module=API&method={}.{}&format=json",
-            method.module, method.action
-        );
+        // Every method is served by /index.php; a synthetic path per method
+        // keeps the spec valid OpenAPI (no query string in path keys)
+        let method_path = format!("/index.php/{}/{}", method.module, method.action);
+
+        // A method listed twice would overwrite the first operation
+        if paths.contains_key(&method_path) {
+            warn!(
+                "Duplicate method {}.{}, keeping the first definition",
+                method.module, method.action
+            );
+            continue;
+        }
 
         paths.insert(
             method_path,
@@ -280,21 +642,59 @@ fn build_openapi_spec(methods: &[MatomoMethod], base_url: &str, version: &str) -
         );
     }
 
-    // Collect tags
-    let tags: Vec<Tag> = tags_set.into_values().collect();
+    let schemas = promote_shared_schemas(&mut paths);
+
+    // Collect tags, sorted by module name
+    tags_set.sort_keys();
+    let tags: Vec<Tag> = tags_set
+        .into_iter()
+        .map(|(module, categories)| {
+            let description = match categories.as_slice() {
+                [] => format!("{} module API methods", module),
+                [category] => format!("{} module API methods (category: {})", module, category),
+                categories => format!(
+                    "{} module API methods (categories: {})",
+                    module,
+                    categories.join(", ")
+                ),
+            };
+            Tag {
+                name: module,
+                description: Some(description),
+            }
+        })
+        .collect();
 
     // Build security schemes
-    let mut security_schemes = HashMap::new();
-    security_schemes.insert(
-        "token_auth".to_string(),
-        SecurityScheme {
-            scheme_type: "apiKey".to_string(),
-            description: Some("Matomo authentication token".to_string()),
-            name: Some("token_auth".to_string()),
-            location: Some("query".to_string()),
-            scheme: None,
-        },
-    );
+    let mut security_schemes = BTreeMap::new();
+    match auth_mode {
+        AuthMode::TokenAuthForm => {
+            security_schemes.insert(
+                "token_auth".to_string(),
+                SecurityScheme {
+                    scheme_type: "apiKey".to_string(),
+                    description: Some("Matomo authentication token".to_string()),
+                    name: Some("token_auth".to_string()),
+                    location: Some("query".to_string()),
+                    scheme: None,
+                },
+            );
+        }
+        AuthMode::BearerHeader => {
+            security_schemes.insert(
+                "bearer_auth".to_string(),
+                SecurityScheme {
+                    scheme_type: "http".to_string(),
+                    description: Some(
+                        "Matomo authentication token sent as a bearer token".to_string(),
+                    ),
+                    name: None,
+                    location: None,
+                    scheme: Some("bearer".to_string()),
+                },
+            );
+        }
+    }
 
     OpenApiSpec {
         openapi: "3.0.3".to_string(),
@@ -313,13 +713,91 @@ fn build_openapi_spec(methods: &[MatomoMethod], base_url: &str, version: &str) -
         }],
         paths,
         components: Some(Components {
-            schemas: None,
+            schemas: (!schemas.is_empty()).then_some(schemas),
             security_schemes: Some(security_schemes),
         }),
         tags: Some(tags),
     }
 }
 
+/// Move row schemas shared by several operations to `components/schemas`
+///
+/// The row is the object schema of an overview report, or the items of a
+/// report with a dimension. Structurally identical rows used by at least two
+/// operations are stored once, named after the first operation using them
+/// (`{Module}{Action}Row`), and replaced by a `$ref`.
+fn promote_shared_schemas(
+    paths: &mut IndexMap<String, PathItem>,
+) -> BTreeMap<String, serde_json::Value> {
+    fn row_schema(schema: &mut serde_json::Value) -> Option<&mut serde_json::Value> {
+        let row = if schema.get("type").and_then(|t| t.as_str()) == Some("array") {
+            schema.get_mut("items")?
+        } else {
+            schema
+        };
+        let has_properties = row
+            .get("properties")
+            .and_then(|p| p.as_object())
+            .is_some_and(|p| !p.is_empty());
+        has_properties.then_some(row)
+    }
+
+    fn response_schema(operation: &mut Operation) -> Option<&mut serde_json::Value> {
+        operation
+            .responses
+            .get_mut("200")?
+            .content
+            .as_mut()?
+            .get_mut("application/json")
+            .map(|media| &mut media.schema)
+    }
+
+    // Distinct rows in order of first use: (row, name, number of uses)
+    let mut rows: Vec<(serde_json::Value, String, usize)> = Vec::new();
+    for operation in paths.values_mut().filter_map(|item| item.get.as_mut()) {
+        let name = schema_name(operation);
+        let Some(row) = response_schema(operation).and_then(row_schema) else {
+            continue;
+        };
+        match rows.iter_mut().find(|(known, _, _)| known == row) {
+            Some((_, _, uses)) => *uses += 1,
+            None => rows.push((row.clone(), name, 1)),
+        }
+    }
+    rows.retain(|(_, _, uses)| *uses > 1);
+
+    for operation in paths.values_mut().filter_map(|item| item.get.as_mut()) {
+        let Some(row) = response_schema(operation).and_then(row_schema) else {
+            continue;
+        };
+        if let Some((_, name, _)) = rows.iter().find(|(known, _, _)| known == row) {
+            *row = serde_json::json!({ "$ref": format!("{}{}", COMPONENT_SCHEMAS_REF, name) });
+        }
+    }
+
+    rows.into_iter().map(|(row, name, _)| (name, row)).collect()
+}
+
+/// Component name of an operation's row schema, e.g. `VisitsSummaryGetRow`
+fn schema_name(operation: &Operation) -> String {
+    let method = operation
+        .matomo_method
+        .as_deref()
+        .unwrap_or(&operation.operation_id);
+    let mut name: String = method
+        .split(['.', '_'])
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect();
+    name.push_str("Row");
+    name
+}
+
 /// Create an OpenAPI operation from a Matomo method
 fn create_operation(method: &MatomoMethod) -> Operation {
     let operation_id = format!("{}_{}", method.module, method.action);
@@ -353,7 +831,7 @@ fn create_operation(method: &MatomoMethod) -> Operation {
             ..Default::default()
         });
 
-    let mut content = HashMap::new();
+    let mut content = BTreeMap::new();
     content.insert(
         "application/json".to_string(),
         crate::openapi::MediaType {
@@ -387,8 +865,11 @@ fn create_operation(method: &MatomoMethod) -> Operation {
 
     Operation {
         operation_id,
+        matomo_method: Some(format!("{}.{}", method.module, method.action)),
+        matomo_module: Some(method.module.clone()),
+        matomo_action: Some(method.action.clone()),
         summary,
-        description: method.description.clone(),
+        description: operation_description(method),
         tags: Some(vec![method.module.clone()]),
         parameters: if parameters.is_empty() {
             None
@@ -396,6 +877,8 @@ fn create_operation(method: &MatomoMethod) -> Operation {
             Some(parameters)
         },
         responses,
+        metric_units: (!method.metric_units.is_empty()).then(|| method.metric_units.clone()),
+        deprecated: method.deprecated,
     }
 }
 
@@ -422,11 +905,41 @@ fn convert_to_openapi_parameter(param: &MatomoParameter) -> Parameter {
             crate::types::ParameterType::Boolean => {
                 serde_json::Value::Bool(d == "true" || d == "1")
             }
+            crate::types::ParameterType::Array => d
+                .split(',')
+                .filter(|item| !item.is_empty())
+                .map(|item| serde_json::Value::String(item.to_string()))
+                .collect(),
             _ => serde_json::Value::String(d.clone()),
         });
 
-    // Add enum values for known parameter types
-    let enum_values = get_enum_values(&param.name);
+    // Add enum values discovered during introspection, or for known parameter types
+    let enum_values = param
+        .enum_values
+        .clone()
+        .or_else(|| get_enum_values(&param.name));
+
+    // Matomo expects lists as one comma-separated value
+    let is_array = param.param_type == crate::types::ParameterType::Array;
+    let items = is_array.then(|| {
+        let (item_type, _) = infer_array_item_type(&param.name).to_openapi_type();
+        Box::new(ParameterSchema {
+            schema_type: item_type.to_string(),
+            format: None,
+            default: None,
+            enum_values: None,
+            items: None,
+            minimum: None,
+            maximum: None,
+        })
+    });
+
+    let example = parameter_example(&param.name)
+        .filter(|example| example_fits(example, schema_type, enum_values.as_deref()));
+    let (minimum, maximum) = match schema_type {
+        "integer" | "number" => parameter_bounds(&param.name),
+        _ => (None, None),
+    };
 
     Parameter {
         name: param.name.clone(),
@@ -438,11 +951,68 @@ fn convert_to_openapi_parameter(param: &MatomoParameter) -> Parameter {
             format: format.map(|s| s.to_string()),
             default,
             enum_values,
+            items,
+            minimum,
+            maximum,
         },
-        example: None,
+        example,
+        explode: is_array.then_some(false),
+        deprecated: param.deprecated,
+    }
+}
+
+/// Inclusive bounds of well-known numeric parameters
+fn parameter_bounds(param_name: &str) -> (Option<i64>, Option<i64>) {
+    match param_name {
+        // -1 disables the limit
+        "filter_limit" => (Some(-1), None),
+        "filter_offset" | "filter_excludelowpop_value" | "filter_truncate" => (Some(0), None),
+        "idSite" | "idGoal" | "lastMinutes" => (Some(1), None),
+        _ => (None, None),
     }
 }
 
+/// Example value of well-known parameters, shown by Swagger UI and to models
+fn parameter_example(param_name: &str) -> Option<serde_json::Value> {
+    let example = match param_name {
+        "idSite" => serde_json::json!(1),
+        "period" => serde_json::json!("day"),
+        "date" => serde_json::json!("yesterday"),
+        "segment" => serde_json::json!("deviceType==desktop"),
+        "filter_limit" => serde_json::json!(10),
+        "filter_offset" => serde_json::json!(0),
+        "filter_sort_column" => serde_json::json!("nb_visits"),
+        "filter_sort_order" => serde_json::json!("desc"),
+        "filter_pattern" => serde_json::json!("/blog"),
+        "filter_column" => serde_json::json!("label"),
+        "language" => serde_json::json!("fr"),
+        "format_metrics" => serde_json::json!(false),
+        _ => return None,
+    };
+    Some(example)
+}
+
+/// Whether an example matches the parameter's schema type and enum
+fn example_fits(
+    example: &serde_json::Value,
+    schema_type: &str,
+    enum_values: Option<&[String]>,
+) -> bool {
+    let type_matches = match schema_type {
+        "integer" => example.is_i64() || example.is_u64(),
+        "number" => example.is_number(),
+        "string" => example.is_string(),
+        "boolean" => example.is_boolean(),
+        _ => false,
+    };
+    type_matches
+        && enum_values.is_none_or(|values| {
+            example
+                .as_str()
+                .is_some_and(|example| values.iter().any(|v| v == example))
+        })
+}
+
 /// Get enum values for known Matomo parameters
 fn get_enum_values(param_name: &str) -> Option<Vec<String>> {
     match param_name {
@@ -465,3 +1035,394 @@ fn get_enum_values(param_name: &str) -> Option<Vec<String>> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::get_common_parameters;
+
+    #[test]
+    fn test_security_scheme_follows_auth_mode() {
+        let spec = build_openapi_spec(&[], "http://localhost", "5.0.0", AuthMode::TokenAuthForm);
+        let schemes = spec.components.unwrap().security_schemes.unwrap();
+        assert_eq!(schemes["token_auth"].scheme_type, "apiKey");
+
+        let spec = build_openapi_spec(&[], "http://localhost", "5.0.0", AuthMode::BearerHeader);
+        let schemes = spec.components.unwrap().security_schemes.unwrap();
+        assert!(!schemes.contains_key("token_auth"));
+        assert_eq!(schemes["bearer_auth"].scheme_type, "http");
+        assert_eq!(schemes["bearer_auth"].scheme.as_deref(), Some("bearer"));
+    }
+
+    #[test]
+    fn test_common_parameters_get_examples() {
+        let examples: HashMap<String, serde_json::Value> = get_common_parameters()
+            .iter()
+            .map(convert_to_openapi_parameter)
+            .filter_map(|param| Some((param.name, param.example?)))
+            .collect();
+
+        assert_eq!(examples["idSite"], 1);
+        assert_eq!(examples["period"], "day");
+        assert_eq!(examples["date"], "yesterday");
+        assert_eq!(examples["segment"], "deviceType==desktop");
+        assert_eq!(examples["filter_limit"], 10);
+        assert_eq!(examples["format_metrics"], false);
+        assert!(!examples.contains_key("format"));
+    }
+
+    #[test]
+    fn test_sort_parameters_are_common() {
+        let parameters = get_common_parameters();
+        let sort_order = parameters
+            .iter()
+            .find(|p| p.name == "filter_sort_order")
+            .map(convert_to_openapi_parameter)
+            .unwrap();
+        assert_eq!(
+            sort_order.schema.enum_values,
+            Some(vec!["asc".to_string(), "desc".to_string()])
+        );
+        assert!(parameters.iter().any(|p| p.name == "filter_sort_column"));
+    }
+
+    #[test]
+    fn test_known_numeric_parameters_have_bounds() {
+        let schema = |name: &str| {
+            get_common_parameters()
+                .iter()
+                .find(|p| p.name == name)
+                .map(convert_to_openapi_parameter)
+                .unwrap()
+                .schema
+        };
+        assert_eq!(schema("filter_limit").minimum, Some(-1));
+        assert_eq!(schema("filter_offset").minimum, Some(0));
+        assert_eq!(schema("filter_offset").maximum, None);
+        assert_eq!(schema("segment").minimum, None);
+    }
+
+    #[test]
+    fn test_examples_respect_type_and_enum() {
+        assert!(example_fits(&serde_json::json!(1), "integer", None));
+        assert!(!example_fits(&serde_json::json!(1), "string", None));
+        let periods = ["week".to_string(), "month".to_string()];
+        assert!(!example_fits(
+            &serde_json::json!("day"),
+            "string",
+            Some(&periods)
+        ));
+        assert!(parameter_example("flat").is_none());
+    }
+
+    #[test]
+    fn test_restrict_formats_constrains_format_enum() {
+        let mut parameters = get_common_parameters();
+        restrict_formats(&mut parameters, &["PNG".to_string()]);
+
+        let format = parameters.iter().find(|p| p.name == "format").unwrap();
+        let openapi_param = convert_to_openapi_parameter(format);
+        assert_eq!(
+            openapi_param.schema.enum_values,
+            Some(vec!["PNG".to_string()])
+        );
+        assert_eq!(openapi_param.schema.default, Some(serde_json::json!("PNG")));
+    }
+
+    #[test]
+    fn test_hierarchical_parameters_only_on_reports_with_subtables() {
+        let hierarchical = get_hierarchical_parameters();
+
+        let mut flat_report = get_common_parameters();
+        add_hierarchical_parameters(&mut flat_report, &hierarchical, false);
+        assert!(!flat_report.iter().any(|p| p.name == "flat"));
+
+        let mut tree_report = get_common_parameters();
+        add_hierarchical_parameters(&mut tree_report, &hierarchical, true);
+        let expanded = tree_report.iter().find(|p| p.name == "expanded").unwrap();
+        assert!(expanded.description.as_ref().unwrap().contains("subtable"));
+
+        // Undocumented parameters from the API reference get the description
+        let mut scraped = vec![convert_parameter(&crate::types::MethodParameter {
+            name: "flat".to_string(),
+            required: false,
+            default: Some(String::new()),
+            known_values: Vec::new(),
+            deprecated: false,
+        })];
+        add_hierarchical_parameters(&mut scraped, &hierarchical, false);
+        assert_eq!(scraped.len(), 1);
+        assert!(scraped[0].description.is_some());
+    }
+
+    #[test]
+    fn test_format_enum_falls_back_to_full_list() {
+        let parameters = get_common_parameters();
+        let format = parameters.iter().find(|p| p.name == "format").unwrap();
+        let openapi_param = convert_to_openapi_parameter(format);
+        assert_eq!(openapi_param.schema.enum_values, get_enum_values("format"));
+    }
+
+    #[test]
+    fn test_metric_units_in_response_schema() {
+        let units = BTreeMap::from([("avg_time_on_site".to_string(), "seconds".to_string())]);
+
+        let overview = metric_response_schema(&units, false).unwrap();
+        let props = overview.properties.unwrap();
+        assert_eq!(
+            props["avg_time_on_site"].description.as_deref(),
+            Some("Unit: seconds")
+        );
+
+        let rows = metric_response_schema(&units, true).unwrap();
+        assert_eq!(rows.schema_type, "array");
+        assert!(metric_response_schema(&BTreeMap::new(), false).is_none());
+
+        // "42%" by default, 0.42 with format_metrics=0
+        let units = BTreeMap::from([("bounce_rate".to_string(), "percentage".to_string())]);
+        let props = metric_response_schema(&units, false)
+            .unwrap()
+            .properties
+            .unwrap();
+        let types: Vec<&str> = props["bounce_rate"]
+            .any_of
+            .iter()
+            .flatten()
+            .map(|variant| variant.schema_type.as_str())
+            .collect();
+        assert_eq!(types, vec!["number", "string"]);
+    }
+
+    #[test]
+    fn test_shared_row_schemas_become_components() {
+        let units = BTreeMap::from([("nb_visits".to_string(), "visits".to_string())]);
+        let method = |module: &str, action: &str, units: &BTreeMap<String, String>| {
+            let mut method = MatomoMethod {
+                name: format!("{}.{}", module, action),
+                module: module.to_string(),
+                action: action.to_string(),
+                parameters: Vec::new(),
+                example_response: None,
+                response_schema: metric_response_schema(units, true),
+                description: None,
+                category: None,
+                order: None,
+                metric_units: units.clone(),
+                metrics: BTreeMap::new(),
+                deprecated: false,
+            };
+            if action == "get" {
+                method.response_schema = metric_response_schema(units, false);
+            }
+            method
+        };
+        let other_units = BTreeMap::from([("bounce_rate".to_string(), "percentage".to_string())]);
+        let methods = [
+            method("Referrers", "getWebsites", &units),
+            method("VisitsSummary", "get", &units),
+            method("Actions", "getPageUrls", &other_units),
+        ];
+        let spec = build_openapi_spec(&methods, "http://localhost", "5.0.0", AuthMode::default());
+
+        let schemas = spec.components.as_ref().unwrap().schemas.as_ref().unwrap();
+        assert_eq!(
+            schemas.keys().collect::<Vec<_>>(),
+            vec!["ReferrersGetWebsitesRow"]
+        );
+        let schema = |path: &str| {
+            spec.paths[path].get.as_ref().unwrap().responses["200"]
+                .content
+                .as_ref()
+                .unwrap()["application/json"]
+                .schema
+                .clone()
+        };
+        assert_eq!(
+            schema("/index.php/VisitsSummary/get")["$ref"],
+            "#/components/schemas/ReferrersGetWebsitesRow"
+        );
+        assert!(schema("/index.php/Actions/getPageUrls")["items"]["properties"].is_object());
+
+        // Tools see the resolved schema
+        let tools = spec.extract_tools();
+        let visits = tools.iter().find(|t| t.module == "VisitsSummary").unwrap();
+        assert_eq!(
+            visits.response_schema.as_ref().unwrap()["properties"]["nb_visits"]["type"],
+            "number"
+        );
+    }
+
+    #[test]
+    fn test_methods_per_module_are_capped() {
+        let method = |module: &str, action: &str, documented: bool| ParsedReportMethod {
+            module: module.to_string(),
+            action: action.to_string(),
+            documentation: documented.then(|| "Report".to_string()),
+            ..Default::default()
+        };
+        let methods = vec![
+            method("CustomDimensions", "getCustomDimension1", false),
+            method("VisitsSummary", "get", false),
+            method("CustomDimensions", "getCustomDimension2", true),
+            method("CustomDimensions", "getCustomDimension3", false),
+        ];
+        let metadata = HashMap::from([(
+            "CustomDimensions.getCustomDimension1".to_string(),
+            MethodMetadata {
+                parameters: Vec::new(),
+                example_url: None,
+                deprecated: true,
+            },
+        )]);
+
+        let kept: Vec<String> = cap_methods_per_module(methods, 2, &metadata)
+            .into_iter()
+            .map(|m| format!("{}.{}", m.module, m.action))
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                "VisitsSummary.get",
+                "CustomDimensions.getCustomDimension2",
+                "CustomDimensions.getCustomDimension3"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_methods_are_grouped_by_category_and_order() {
+        let method =
+            |module: &str, action: &str, category: Option<&str>, order: i64| MatomoMethod {
+                name: format!("{}.{}", module, action),
+                module: module.to_string(),
+                action: action.to_string(),
+                parameters: Vec::new(),
+                example_response: None,
+                response_schema: None,
+                description: None,
+                category: category.map(str::to_string),
+                order: Some(order),
+                metric_units: BTreeMap::new(),
+                metrics: BTreeMap::new(),
+                deprecated: false,
+            };
+        let methods = [
+            method("API", "getMatomoVersion", None, 1),
+            method("Referrers", "getWebsites", Some("Referrers"), 20),
+            method("VisitsSummary", "get", Some("Visitors"), 1),
+            method("Referrers", "getAll", Some("Referrers"), 10),
+            method("UserCountry", "getCountry", Some("Visitors"), 5),
+        ];
+        let spec = build_openapi_spec(&methods, "http://localhost", "5.0.0", AuthMode::default());
+
+        let names: Vec<String> = spec.extract_tools().into_iter().map(|t| t.name).collect();
+        assert_eq!(
+            names,
+            vec![
+                "Referrers_getAll",
+                "Referrers_getWebsites",
+                "VisitsSummary_get",
+                "UserCountry_getCountry",
+                "API_getMatomoVersion"
+            ]
+        );
+        let tags = spec.tags.unwrap();
+        assert_eq!(
+            tags[0].description.as_deref(),
+            Some("API module API methods")
+        );
+        assert_eq!(
+            tags[1].description.as_deref(),
+            Some("Referrers module API methods (category: Referrers)")
+        );
+    }
+
+    #[test]
+    fn test_spec_ordering_does_not_depend_on_input_order() {
+        let method = |module: &str, action: &str| MatomoMethod {
+            name: format!("{}.{}", module, action),
+            module: module.to_string(),
+            action: action.to_string(),
+            parameters: get_common_parameters(),
+            example_response: None,
+            response_schema: None,
+            description: None,
+            category: None,
+            order: None,
+            metric_units: BTreeMap::new(),
+            metrics: BTreeMap::new(),
+            deprecated: false,
+        };
+        let mut methods = vec![
+            method("VisitsSummary", "get"),
+            method("Actions", "getPageUrls"),
+            method("Actions", "get"),
+            method("Referrers", "getAll"),
+        ];
+        let render = |methods: &[MatomoMethod]| {
+            let spec =
+                build_openapi_spec(methods, "http://localhost", "5.0.0", AuthMode::default());
+            serde_json::to_string(&spec).unwrap()
+        };
+
+        let first = render(&methods);
+        methods.reverse();
+        assert_eq!(render(&methods), first);
+
+        let spec: OpenApiSpec = serde_json::from_str(&first).unwrap();
+        let tags: Vec<&str> = spec
+            .tags
+            .iter()
+            .flatten()
+            .map(|t| t.name.as_str())
+            .collect();
+        assert_eq!(tags, vec!["Actions", "Referrers", "VisitsSummary"]);
+        let tools: Vec<String> = spec.extract_tools().into_iter().map(|t| t.name).collect();
+        assert_eq!(
+            tools,
+            vec![
+                "Actions_get",
+                "Actions_getPageUrls",
+                "Referrers_getAll",
+                "VisitsSummary_get"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_paths_have_no_query_string() {
+        let method = MatomoMethod {
+            name: "VisitsSummary.get".to_string(),
+            module: "VisitsSummary".to_string(),
+            action: "get".to_string(),
+            parameters: get_common_parameters(),
+            example_response: None,
+            response_schema: None,
+            description: None,
+            category: None,
+            order: None,
+            metric_units: BTreeMap::new(),
+            metrics: BTreeMap::from([
+                ("nb_visits".to_string(), "Visits".to_string()),
+                ("bounce_rate".to_string(), "Bounce Rate".to_string()),
+            ]),
+            deprecated: false,
+        };
+        let spec = build_openapi_spec(&[method], "http://localhost", "5.0.0", AuthMode::default());
+
+        let (path, item) = spec.paths.first().unwrap();
+        assert_eq!(path, "/index.php/VisitsSummary/get");
+        assert_eq!(
+            item.get.as_ref().unwrap().matomo_method.as_deref(),
+            Some("VisitsSummary.get")
+        );
+
+        let tools = spec.extract_tools();
+        assert_eq!(tools[0].module, "VisitsSummary");
+        assert_eq!(tools[0].action, "get");
+        assert_eq!(
+            tools[0].description,
+            "Returns metrics: bounce_rate (Bounce Rate), nb_visits (Visits)"
+        );
+    }
+}
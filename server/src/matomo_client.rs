@@ -1,83 +1,403 @@
 use anyhow::{Context, Result};
-use reqwest::Client;
+use reqwest::header::{HeaderValue, COOKIE};
+use reqwest::{Client, StatusCode};
 use std::collections::HashMap;
-use tracing::debug;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
 use url::Url;
 
-use crate::http_client::build_client;
-use reqwest::header::HeaderMap;
+use crate::cache::ResponseCache;
+use crate::error::MatomoError;
+use crate::http_client::{build_client, AuthMode, HttpConfig};
+
+/// Page size used by `call_method_paginated` when no `filter_limit` is given
+pub const DEFAULT_PAGE_SIZE: u64 = 500;
+
+/// Upper bound on the number of pages fetched by `call_method_paginated`
+pub const MAX_PAGES: u64 = 50;
+
+/// How often and how long `call_method` waits on transient errors (report archiving)
+#[derive(Debug, Clone, Copy)]
+pub struct RetryConfig {
+    /// Retries after the first attempt; 0 surfaces the error at once
+    pub retries: u32,
+    /// Pause before each retry
+    pub delay: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            retries: DEFAULT_ARCHIVING_RETRIES,
+            delay: Duration::from_secs(DEFAULT_ARCHIVING_RETRY_DELAY_SECS),
+        }
+    }
+}
+
+/// Default number of retries of a call answered "archiving in progress"
+pub const DEFAULT_ARCHIVING_RETRIES: u32 = 2;
+
+/// Default pause before retrying such a call
+pub const DEFAULT_ARCHIVING_RETRY_DELAY_SECS: u64 = 3;
+
+/// Responses with a larger body are not cached
+///
+/// Caching keeps a copy of the parsed response for every entry; for large
+/// reports that copy would cost more memory than the refetch saves.
+pub const MAX_CACHED_BODY_BYTES: usize = 1024 * 1024;
+
+/// Site ids the token can view, with the time they were fetched
+type AccessibleSites = (Instant, Vec<u64>);
 
 /// HTTP client for making Matomo API calls
 #[derive(Debug, Clone)]
 pub struct MatomoClient {
     client: Client,
     base_url: Url,
-    token_auth: Option<String>,
+    /// API endpoint derived from the base URL and the configured API path
+    endpoint: Url,
+    /// Shared so the token can be rotated while requests are in flight
+    token_auth: Arc<RwLock<Option<String>>>,
+    auth_mode: AuthMode,
+    /// Optional cache of successful responses
+    cache: Option<Arc<ResponseCache>>,
+    /// Session cookies sent as the `Cookie` header, alongside any token
+    cookies: Option<HeaderValue>,
+    /// Debug-log request forms and response bodies (truncated to this size)
+    trace_body_bytes: Option<usize>,
+    /// Retries of calls failing with a transient Matomo error
+    retry: RetryConfig,
+    /// Return bare scalar JSON responses as `{"value": ...}`
+    wrap_scalars: bool,
+    /// Sites the token can view and when they were fetched; reset with the token
+    accessible_sites: Arc<RwLock<Option<AccessibleSites>>>,
+    /// Parameters added to every request unless the call sets them
+    extra_params: Vec<(String, String)>,
 }
 
 impl MatomoClient {
     /// Create a new Matomo client
-    pub fn new(base_url: &str, token: Option<String>, extra_headers: &HeaderMap) -> Result<Self> {
+    pub fn new(
+        base_url: &str,
+        token: Option<String>,
+        auth_mode: AuthMode,
+        http: &HttpConfig,
+    ) -> Result<Self> {
         let base_url = Url::parse(base_url).context("Invalid base URL")?;
 
         // Use shared HTTP client with custom User-Agent and extra headers
-        let client = build_client(false, extra_headers)?;
+        let client = build_client(http)?;
 
         Ok(Self {
             client,
+            endpoint: http.api_endpoint(&base_url),
             base_url,
-            token_auth: token,
+            token_auth: Arc::new(RwLock::new(token)),
+            auth_mode,
+            cache: None,
+            cookies: None,
+            trace_body_bytes: http.trace_body_bytes,
+            retry: RetryConfig::default(),
+            wrap_scalars: true,
+            accessible_sites: Arc::new(RwLock::new(None)),
+            extra_params: http.extra_params.clone(),
         })
     }
 
+    /// Send `cookies` (e.g. "MATOMO_SESSID=...") with every request
+    pub fn with_cookies(mut self, cookies: &str) -> Result<Self> {
+        let mut value =
+            HeaderValue::from_str(cookies.trim()).context("Invalid characters in cookies")?;
+        value.set_sensitive(true);
+        self.cookies = Some(value);
+        Ok(self)
+    }
+
+    /// Serve identical calls from `cache` while its entries are fresh
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Retry calls answered with a transient error (see `RetryConfig`)
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Whether bare scalar JSON responses (e.g. `VisitsSummary.getVisits`) are
+    /// returned as `{"value": ...}`, the default, or as is
+    pub fn with_wrap_scalars(mut self, wrap_scalars: bool) -> Self {
+        self.wrap_scalars = wrap_scalars;
+        self
+    }
+
+    /// Base URL of the Matomo instance
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    /// How the token is sent to Matomo
+    pub fn auth_mode(&self) -> AuthMode {
+        self.auth_mode
+    }
+
+    /// GET URL of a method call, with a `{token_auth}` placeholder for the token
+    ///
+    /// Meant to be copied into a browser or a cron job: parameters are sorted
+    /// and the format defaults to JSON, as in `call_method`. In bearer mode the
+    /// token goes in a header, so the URL has no placeholder.
+    pub fn api_url(
+        &self,
+        module: &str,
+        action: &str,
+        params: HashMap<String, serde_json::Value>,
+    ) -> String {
+        let mut params: Vec<(String, String)> = params
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, form_value(value)?)))
+            .collect();
+        if !params.iter().any(|(key, _)| key == "format") {
+            params.push(("format".to_string(), "JSON".to_string()));
+        }
+        add_extra_params(&mut params, &self.extra_params);
+        params.sort();
+
+        let mut url = self.endpoint.clone();
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("module", "API")
+            .append_pair("method", &format!("{}.{}", module, action))
+            .extend_pairs(params)
+            .finish();
+        match self.auth_mode {
+            // The placeholder stays readable instead of being percent-encoded
+            AuthMode::TokenAuthForm => {
+                url.set_query(Some(&format!("{}&token_auth={{token_auth}}", query)))
+            }
+            AuthMode::BearerHeader => url.set_query(Some(&query)),
+        }
+        url.to_string()
+    }
+
+    /// Current API token
+    fn token(&self) -> Option<String> {
+        self.token_auth
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Replace the API token, returning whether it changed
+    pub fn set_token(&self, token: Option<String>) -> bool {
+        let mut current = self.token_auth.write().unwrap_or_else(|e| e.into_inner());
+        if *current == token {
+            return false;
+        }
+        *current = token;
+        *self
+            .accessible_sites
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = None;
+        // Cached responses reflect what the previous token could see
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+        true
+    }
+
     /// Call a Matomo API method
+    ///
+    /// The response is parsed as JSON unless the caller passes a `format`
+    /// parameter other than JSON (e.g. CSV, TSV, XML), in which case the raw
+    /// body is returned as a JSON string. Successful responses are cached when
+    /// a cache is configured; Matomo errors and bodies larger than
+    /// `MAX_CACHED_BODY_BYTES` never are.
+    ///
+    /// Transient errors, such as a report still being archived, are retried
+    /// as configured by `with_retry` before being returned.
+    ///
+    /// The body is buffered, so the peak memory of a call is about the body
+    /// plus its parsed form; the body is released as soon as it is parsed.
     pub async fn call_method(
         &self,
         module: &str,
         action: &str,
         params: HashMap<String, serde_json::Value>,
     ) -> Result<serde_json::Value> {
-        let mut url = self.base_url.clone();
-        url.set_path("index.php");
+        self.call(module, action, params, true, &mut None).await
+    }
+
+    /// `call_method` bypassing the response cache, for calls that must reach
+    /// Matomo: methods that may modify data, or checks of the live state
+    pub async fn call_method_uncached(
+        &self,
+        module: &str,
+        action: &str,
+        params: HashMap<String, serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        self.call(module, action, params, false, &mut None).await
+    }
+
+    /// `call_method`, also returning the HTTP status of the last Matomo response
+    ///
+    /// The status is `None` for cached values and when no response arrived.
+    pub async fn call_method_with_status(
+        &self,
+        module: &str,
+        action: &str,
+        params: HashMap<String, serde_json::Value>,
+    ) -> (Result<serde_json::Value>, Option<StatusCode>) {
+        let mut status = None;
+        let result = self.call(module, action, params, true, &mut status).await;
+        (result, status)
+    }
 
+    async fn call(
+        &self,
+        module: &str,
+        action: &str,
+        mut params: HashMap<String, serde_json::Value>,
+        use_cache: bool,
+        status: &mut Option<StatusCode>,
+    ) -> Result<serde_json::Value> {
         let method_str = format!("{}.{}", module, action);
+        let cache = self.cache.as_ref().filter(|_| use_cache);
+        let cache_key = cache.map(|_| ResponseCache::key(module, action, &params));
+        if let (Some(cache), Some(key)) = (cache, &cache_key) {
+            if let Some(value) = cache.get(key) {
+                debug!("Cache hit: {}", method_str);
+                return Ok(value);
+            }
+        }
+
         debug!("Calling Matomo API: {}", method_str);
 
+        let format = match params.remove("format") {
+            Some(serde_json::Value::String(f)) if !f.is_empty() => f,
+            _ => "JSON".to_string(),
+        };
+        let is_json = format.eq_ignore_ascii_case("json");
+
         // Build form parameters
         let mut form_params: Vec<(String, String)> = vec![
             ("module".to_string(), "API".to_string()),
-            ("method".to_string(), method_str),
-            ("format".to_string(), "JSON".to_string()),
+            ("method".to_string(), method_str.clone()),
+            ("format".to_string(), format),
         ];
 
-        // Add token if available
-        if let Some(ref token) = self.token_auth {
-            form_params.push(("token_auth".to_string(), token.clone()));
-        }
-
         // Add user-provided parameters
         for (key, value) in params {
-            let str_value = match value {
-                serde_json::Value::String(s) => s,
-                serde_json::Value::Number(n) => n.to_string(),
-                serde_json::Value::Bool(b) => {
-                    if b {
-                        "1".to_string()
-                    } else {
-                        "0".to_string()
+            if let Some(str_value) = form_value(value) {
+                form_params.push((key, str_value));
+            }
+        }
+
+        let mut retries_left = self.retry.retries;
+        let (value, cacheable) = loop {
+            let result = match self.post(form_params.clone()).await {
+                Ok((response_status, text)) => {
+                    *status = Some(response_status);
+                    let cacheable = text.len() <= MAX_CACHED_BODY_BYTES;
+                    parse_response(text, is_json, self.wrap_scalars).map(|value| (value, cacheable))
+                }
+                Err(e) => {
+                    // Non-success statuses fail in `post`, the status kept on the error
+                    if let Some(error) = e.downcast_ref::<MatomoError>() {
+                        *status = error.status.or(*status);
                     }
+                    Err(e)
                 }
-                serde_json::Value::Null => continue,
-                other => other.to_string(),
             };
-            form_params.push((key, str_value));
+            match result {
+                Err(e) if retries_left > 0 && is_transient(&e) => {
+                    retries_left -= 1;
+                    warn!(
+                        "{}: {:#}; retrying in {}s ({} retries left)",
+                        method_str,
+                        e,
+                        self.retry.delay.as_secs(),
+                        retries_left
+                    );
+                    tokio::time::sleep(self.retry.delay).await;
+                }
+                result => break result?,
+            }
+        };
+
+        if let (Some(cache), Some(key), true) = (cache, cache_key, cacheable) {
+            cache.insert(key, value.clone());
+        }
+
+        Ok(value)
+    }
+
+    /// Call several Matomo API methods in one `API.getBulkRequest` round trip
+    ///
+    /// Each call is encoded as a `urls[i]` query string. Returns the array of
+    /// results, in call order; a failed call yields its Matomo error object
+    /// in place of a result (see `item_errors`). Fails if every call failed.
+    pub async fn call_bulk(
+        &self,
+        calls: Vec<(String, String, HashMap<String, serde_json::Value>)>,
+    ) -> Result<serde_json::Value> {
+        debug!(
+            "Calling Matomo API: API.getBulkRequest ({} calls)",
+            calls.len()
+        );
+
+        let mut form_params: Vec<(String, String)> = vec![
+            ("module".to_string(), "API".to_string()),
+            ("method".to_string(), "API.getBulkRequest".to_string()),
+            ("format".to_string(), "JSON".to_string()),
+        ];
+
+        for (index, (module, action, params)) in calls.into_iter().enumerate() {
+            let query = bulk_query(&module, &action, params, &self.extra_params);
+            form_params.push((format!("urls[{}]", index), query));
+        }
+
+        let (_, text) = self.post(form_params).await?;
+        let json: serde_json::Value = serde_json::from_str(&text)
+            .with_context(|| format!("Invalid bulk response from Matomo: {}", text))?;
+        check_error(&json)?;
+
+        Ok(json)
+    }
+
+    /// POST form parameters to the API endpoint and return the response status and body
+    ///
+    /// Adds the token (as a form field or bearer header, depending on the
+    /// auth mode) and fails on non-success HTTP statuses.
+    async fn post(&self, mut form_params: Vec<(String, String)>) -> Result<(StatusCode, String)> {
+        let url = self.endpoint.clone();
+        add_extra_params(&mut form_params, &self.extra_params);
+
+        // Add token if available (bearer mode sends it as a header instead)
+        let token_auth = self.token();
+        if let Some(ref token) = token_auth {
+            if self.auth_mode == AuthMode::TokenAuthForm {
+                form_params.push(("token_auth".to_string(), token.clone()));
+            }
+        }
+
+        if self.trace_body_bytes.is_some() {
+            debug!(
+                "Matomo request form: {}",
+                redacted_form(&form_params, &self.extra_params)
+            );
         }
 
         // Make POST request (required for token_auth)
-        let response = self
-            .client
-            .post(url.as_str())
-            .form(&form_params)
+        let mut request = self.client.post(url.as_str()).form(&form_params);
+        if let (Some(token), AuthMode::BearerHeader) = (&token_auth, self.auth_mode) {
+            request = request.bearer_auth(token);
+        }
+        if let Some(ref cookies) = self.cookies {
+            request = request.header(COOKIE, cookies.clone());
+        }
+
+        let response = request
             .send()
             .await
             .context("Failed to send request to Matomo")?;
@@ -85,25 +405,543 @@ impl MatomoClient {
         let status = response.status();
         let text = response.text().await.context("Failed to read response")?;
 
+        if let Some(max_bytes) = self.trace_body_bytes {
+            debug!(
+                "Matomo response ({}, {} bytes): {}",
+                status,
+                text.len(),
+                truncate_bytes(&text, max_bytes)
+            );
+        }
+
         if !status.is_success() {
-            anyhow::bail!("Matomo API error ({}): {}", status, text);
+            return Err(MatomoError::from_status(status, &text).into());
         }
 
-        // Try to parse as JSON
-        let json: serde_json::Value =
-            serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text.clone()));
+        Ok((status, text))
+    }
 
-        // Check for Matomo error response
-        if let Some(obj) = json.as_object() {
-            if obj.get("result").and_then(|v| v.as_str()) == Some("error") {
-                let message = obj
-                    .get("message")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("Unknown error");
-                anyhow::bail!("Matomo API error: {}", message);
+    /// Ids of the sites the token has at least view access to
+    ///
+    /// Wraps `SitesManager.getSitesIdWithAtLeastViewAccess`. The list is
+    /// fetched once per token; a copy older than `max_age`, when given, is
+    /// fetched again (e.g. to see a site created since).
+    pub async fn accessible_site_ids(&self, max_age: Option<Duration>) -> Result<Vec<u64>> {
+        let cached = self
+            .accessible_sites
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if let Some((fetched, ids)) = cached {
+            if max_age.is_none_or(|max_age| fetched.elapsed() <= max_age) {
+                return Ok(ids);
             }
         }
 
-        Ok(json)
+        let response = self
+            .call_method_uncached(
+                "SitesManager",
+                "getSitesIdWithAtLeastViewAccess",
+                HashMap::new(),
+            )
+            .await?;
+        let ids = parse_site_ids(&response)
+            .context("Unexpected getSitesIdWithAtLeastViewAccess response")?;
+        *self
+            .accessible_sites
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = Some((Instant::now(), ids.clone()));
+        Ok(ids)
+    }
+
+    /// Names of the segment dimensions available for a site
+    ///
+    /// Reads the `segment` field of every entry of `API.getSegmentsMetadata`.
+    pub async fn segment_dimensions(&self, id_site: Option<&str>) -> Result<Vec<String>> {
+        let mut params = HashMap::new();
+        if let Some(id_site) = id_site {
+            params.insert("idSites".to_string(), id_site.into());
+        }
+
+        let metadata = self
+            .call_method("API", "getSegmentsMetadata", params)
+            .await?;
+        let mut dimensions: Vec<String> = metadata
+            .as_array()
+            .context("Unexpected getSegmentsMetadata response")?
+            .iter()
+            .filter_map(|entry| entry.get("segment")?.as_str())
+            .map(|s| s.to_string())
+            .collect();
+        dimensions.sort();
+        dimensions.dedup();
+        Ok(dimensions)
+    }
+
+    /// Current report metadata of one method, one entry per report variant
+    ///
+    /// Wraps `API.getMetadata`, which returns the `getReportMetadata` entries
+    /// of a single report; empty when the method is not a report.
+    pub async fn report_metadata(
+        &self,
+        module: &str,
+        action: &str,
+        id_site: &str,
+    ) -> Result<serde_json::Value> {
+        let params = HashMap::from([
+            ("apiModule".to_string(), module.into()),
+            ("apiAction".to_string(), action.into()),
+            ("idSite".to_string(), id_site.into()),
+        ]);
+        self.call_method("API", "getMetadata", params).await
+    }
+
+    /// Values recently seen for a segment dimension (e.g. `deviceType`)
+    ///
+    /// Wraps `API.getSuggestedValuesForSegment`; an empty list means Matomo
+    /// has not collected values for the dimension yet.
+    pub async fn segment_values(
+        &self,
+        segment_name: &str,
+        id_site: &str,
+    ) -> Result<Vec<serde_json::Value>> {
+        let params = HashMap::from([
+            ("segmentName".to_string(), segment_name.into()),
+            ("idSite".to_string(), id_site.into()),
+        ]);
+
+        let values = self
+            .call_method("API", "getSuggestedValuesForSegment", params)
+            .await?;
+        match values {
+            serde_json::Value::Array(values) => Ok(values),
+            // PHP serializes an empty list as an empty object
+            serde_json::Value::Object(obj) if obj.is_empty() => Ok(Vec::new()),
+            serde_json::Value::Object(obj) => Ok(obj.into_iter().map(|(_, v)| v).collect()),
+            _ => anyhow::bail!("Unexpected getSuggestedValuesForSegment response"),
+        }
+    }
+
+    /// Call a Matomo report method and fetch every page of rows
+    ///
+    /// Increments `filter_offset` by the page size (the caller's `filter_limit`,
+    /// or `DEFAULT_PAGE_SIZE`) until a page returns fewer rows than requested,
+    /// concatenating the JSON arrays. Stops after `MAX_PAGES` pages. Responses
+    /// that are not arrays are returned as-is after the first page.
+    pub async fn call_method_paginated(
+        &self,
+        module: &str,
+        action: &str,
+        params: HashMap<String, serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        let page_size = params
+            .get("filter_limit")
+            .and_then(value_as_u64)
+            .filter(|&n| n > 0)
+            .unwrap_or(DEFAULT_PAGE_SIZE);
+        let mut offset = params
+            .get("filter_offset")
+            .and_then(value_as_u64)
+            .unwrap_or(0);
+
+        let mut rows = Vec::new();
+        for page in 0..MAX_PAGES {
+            let mut page_params = params.clone();
+            page_params.insert("filter_limit".to_string(), page_size.into());
+            page_params.insert("filter_offset".to_string(), offset.into());
+
+            let result = self.call_method(module, action, page_params).await?;
+            let serde_json::Value::Array(page_rows) = result else {
+                return Ok(result);
+            };
+
+            let count = page_rows.len() as u64;
+            rows.extend(page_rows);
+
+            if count < page_size {
+                break;
+            }
+            if page + 1 == MAX_PAGES {
+                warn!(
+                    "Stopped paginating {}.{} after {} pages ({} rows)",
+                    module,
+                    action,
+                    MAX_PAGES,
+                    rows.len()
+                );
+            }
+            offset += page_size;
+        }
+
+        Ok(serde_json::Value::Array(rows))
+    }
+}
+
+/// Append the `--extra-param` parameters a request does not already set
+fn add_extra_params(params: &mut Vec<(String, String)>, extra_params: &[(String, String)]) {
+    for (key, value) in extra_params {
+        if !params.iter().any(|(name, _)| name == key) {
+            params.push((key.clone(), value.clone()));
+        }
+    }
+}
+
+/// Query string of one `API.getBulkRequest` call, `--extra-param` parameters included
+///
+/// Matomo runs each `urls[i]` as a request of its own, which does not see the
+/// parameters of the outer request.
+fn bulk_query(
+    module: &str,
+    action: &str,
+    params: HashMap<String, serde_json::Value>,
+    extra_params: &[(String, String)],
+) -> String {
+    let mut pairs = vec![("method".to_string(), format!("{}.{}", module, action))];
+    for (key, value) in params {
+        // The bulk response is always JSON
+        if key == "format" {
+            continue;
+        }
+        if let Some(str_value) = form_value(value) {
+            pairs.push((key, str_value));
+        }
+    }
+    add_extra_params(&mut pairs, extra_params);
+
+    let mut query = url::form_urlencoded::Serializer::new(String::new());
+    query.extend_pairs(pairs);
+    query.finish()
+}
+
+/// Site ids of a `getSitesIdWithAtLeastViewAccess` response, numbers or numeric strings
+fn parse_site_ids(response: &serde_json::Value) -> Option<Vec<u64>> {
+    response
+        .as_array()?
+        .iter()
+        .map(|id| id.as_u64().or_else(|| id.as_str()?.parse().ok()))
+        .collect()
+}
+
+/// Serialize an argument as a form value (`None` for nulls)
+fn form_value(value: serde_json::Value) -> Option<String> {
+    let str_value = match value {
+        serde_json::Value::String(s) => s,
+        serde_json::Value::Number(n) => n.to_string(),
+        serde_json::Value::Bool(b) => {
+            if b {
+                "1".to_string()
+            } else {
+                "0".to_string()
+            }
+        }
+        serde_json::Value::Null => return None,
+        // Matomo expects lists as a comma-separated value
+        serde_json::Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(","),
+        other => other.to_string(),
+    };
+    Some(str_value)
+}
+
+/// Form parameters as `key=value&...` with the token and the `--extra-param`
+/// values replaced, as the latter may hold secrets too
+fn redacted_form(form_params: &[(String, String)], extra_params: &[(String, String)]) -> String {
+    form_params
+        .iter()
+        .map(|(key, value)| {
+            let is_extra = extra_params.iter().any(|(k, v)| k == key && v == value);
+            let value = if key == "token_auth" || is_extra {
+                "[redacted]"
+            } else {
+                value.as_str()
+            };
+            format!("{}={}", key, value)
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// At most `max_bytes` of `text`, cut on a character boundary
+fn truncate_bytes(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let end = (0..=max_bytes)
+        .rev()
+        .find(|&i| text.is_char_boundary(i))
+        .unwrap_or(0);
+    &text[..end]
+}
+
+/// Text of a scalar response, given bare (`"5.1.0"`) or as `{"value": "5.1.0"}`
+///
+/// Matomo answers scalar methods (e.g. `API.getMatomoVersion`) either way
+/// depending on its version and the method.
+pub fn extract_scalar(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::Bool(b) => Some(b.to_string()),
+        serde_json::Value::Object(obj) if obj.len() == 1 => {
+            obj.get("value").and_then(extract_scalar)
+        }
+        _ => None,
+    }
+}
+
+/// Give bare scalar JSON responses the `{"value": ...}` shape of the others
+fn wrap_scalar(json: serde_json::Value) -> serde_json::Value {
+    match json {
+        serde_json::Value::String(_)
+        | serde_json::Value::Number(_)
+        | serde_json::Value::Bool(_) => {
+            serde_json::json!({ "value": json })
+        }
+        other => other,
+    }
+}
+
+/// Parse a response body, consuming it so it is freed once parsed
+///
+/// Non-JSON formats, and JSON bodies that fail to parse, are passed through
+/// untouched as a string. Scalar JSON bodies are wrapped if `wrap_scalars`.
+fn parse_response(text: String, is_json: bool, wrap_scalars: bool) -> Result<serde_json::Value> {
+    if !is_json {
+        return Ok(serde_json::Value::String(text));
+    }
+    match serde_json::from_str(&text) {
+        Ok(json) => {
+            drop(text);
+            check_error(&json)?;
+            Ok(if wrap_scalars {
+                wrap_scalar(json)
+            } else {
+                json
+            })
+        }
+        Err(_) => Ok(serde_json::Value::String(text)),
+    }
+}
+
+/// Message of a Matomo error object (`{"result": "error", "message": ...}`)
+fn error_message(json: &serde_json::Value) -> Option<&str> {
+    let obj = json.as_object()?;
+    (obj.get("result").and_then(|v| v.as_str()) == Some("error")).then(|| {
+        obj.get("message")
+            .and_then(|v| v.as_str())
+            .unwrap_or("Unknown error")
+    })
+}
+
+/// Index and message of the error objects of an array response
+///
+/// Bulk responses hold one element per call, so a failed call shows up as an
+/// error element among successful results.
+pub fn item_errors(json: &serde_json::Value) -> Vec<(usize, String)> {
+    json.as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .filter_map(|(index, item)| Some((index, error_message(item)?.to_string())))
+        .collect()
+}
+
+/// Whether a failed call may succeed when sent again (e.g. report being archived)
+fn is_transient(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<MatomoError>()
+        .is_some_and(|error| error.kind.is_transient())
+}
+
+/// Fail on a Matomo error response, or an array made only of errors
+fn check_error(json: &serde_json::Value) -> Result<()> {
+    if let Some(message) = error_message(json) {
+        return Err(MatomoError::from_message(message).into());
+    }
+    // Stops at the first successful item, so large reports are not scanned
+    if let Some(items) = json.as_array().filter(|items| !items.is_empty()) {
+        if items.iter().all(|item| error_message(item).is_some()) {
+            let message = error_message(&items[0]).unwrap_or_default();
+            return Err(MatomoError::from_message(message).into());
+        }
+    }
+    Ok(())
+}
+
+/// Read a non-negative integer from a JSON number or numeric string
+fn value_as_u64(value: &serde_json::Value) -> Option<u64> {
+    match value {
+        serde_json::Value::Number(n) => n.as_u64(),
+        serde_json::Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_scalar_responses_are_normalized() {
+        assert_eq!(extract_scalar(&json!("5.1.0")).as_deref(), Some("5.1.0"));
+        assert_eq!(
+            extract_scalar(&json!({"value": "5.1.0"})).as_deref(),
+            Some("5.1.0")
+        );
+        assert_eq!(extract_scalar(&json!({"value": 42})).as_deref(), Some("42"));
+        assert_eq!(extract_scalar(&json!({"value": 1, "other": 2})), None);
+        assert_eq!(extract_scalar(&json!([1])), None);
+
+        assert_eq!(wrap_scalar(json!(42)), json!({"value": 42}));
+        assert_eq!(wrap_scalar(json!({"value": 42})), json!({"value": 42}));
+        assert_eq!(wrap_scalar(json!([1, 2])), json!([1, 2]));
+    }
+
+    #[test]
+    fn test_scalar_wrapping_can_be_disabled() {
+        let parse = |text: &str, is_json, wrap| parse_response(text.to_string(), is_json, wrap);
+        assert_eq!(parse("42", true, true).unwrap(), json!({"value": 42}));
+        assert_eq!(parse("42", true, false).unwrap(), json!(42));
+        assert_eq!(parse("[1]", true, true).unwrap(), json!([1]));
+        // Raw formats are never wrapped
+        assert_eq!(parse("42", false, true).unwrap(), json!("42"));
+    }
+
+    #[test]
+    fn test_bulk_errors_are_detected() {
+        let partial = json!([
+            {"nb_visits": 12},
+            {"result": "error", "message": "The period 'daily' is not supported."}
+        ]);
+        assert!(check_error(&partial).is_ok());
+        assert_eq!(
+            item_errors(&partial),
+            vec![(1, "The period 'daily' is not supported.".to_string())]
+        );
+
+        let failed = json!([{"result": "error", "message": "You can't access this resource"}]);
+        let err = check_error(&failed).unwrap_err();
+        assert!(err.to_string().contains("can't access"), "{}", err);
+
+        assert!(check_error(&json!([])).is_ok());
+        assert!(item_errors(&json!({"value": 1})).is_empty());
+    }
+
+    #[test]
+    fn test_api_url_uses_a_token_placeholder() {
+        let client = MatomoClient::new(
+            "https://matomo.example.com",
+            Some("secret".to_string()),
+            AuthMode::default(),
+            &HttpConfig::default(),
+        )
+        .unwrap();
+        let params = HashMap::from([
+            ("idSite".to_string(), json!(1)),
+            ("segment".to_string(), json!("deviceType==desktop")),
+        ]);
+        assert_eq!(
+            client.api_url("VisitsSummary", "get", params),
+            "https://matomo.example.com/index.php?module=API&method=VisitsSummary.get\
+             &format=JSON&idSite=1&segment=deviceType%3D%3Ddesktop&token_auth={token_auth}"
+        );
+    }
+
+    #[test]
+    fn test_api_url_has_no_placeholder_in_bearer_mode() {
+        let client = MatomoClient::new(
+            "https://matomo.example.com",
+            Some("secret".to_string()),
+            AuthMode::BearerHeader,
+            &HttpConfig::default(),
+        )
+        .unwrap();
+        let url = client.api_url("API", "getMatomoVersion", HashMap::new());
+        assert_eq!(
+            url,
+            "https://matomo.example.com/index.php?module=API&method=API.getMatomoVersion&format=JSON"
+        );
+    }
+
+    #[test]
+    fn test_api_url_includes_extra_params() {
+        let http = HttpConfig::default().with_extra_params(vec![
+            ("force_api_session".to_string(), "1".to_string()),
+            ("idSite".to_string(), "9".to_string()),
+        ]);
+        let client = MatomoClient::new(
+            "https://matomo.example.com",
+            None,
+            AuthMode::default(),
+            &http,
+        )
+        .unwrap();
+        let url = client.api_url(
+            "VisitsSummary",
+            "get",
+            HashMap::from([("idSite".to_string(), json!(1))]),
+        );
+        assert!(url.contains("&force_api_session=1&"), "{}", url);
+        assert!(
+            url.contains("idSite=1") && !url.contains("idSite=9"),
+            "{}",
+            url
+        );
+    }
+
+    #[test]
+    fn test_parse_site_ids() {
+        assert_eq!(parse_site_ids(&json!([1, "3", 7])), Some(vec![1, 3, 7]));
+        assert_eq!(parse_site_ids(&json!([])), Some(Vec::new()));
+        assert_eq!(parse_site_ids(&json!({"value": 1})), None);
+    }
+
+    #[test]
+    fn test_regex_values_are_sent_verbatim() {
+        // The form encoder escapes once; Matomo decodes back to the regex
+        let pattern = r"^/blog/(2024|2025)\.html?$";
+        assert_eq!(form_value(json!(pattern)).as_deref(), Some(pattern));
+    }
+
+    #[test]
+    fn test_booleans_are_sent_as_flags() {
+        // Matomo reads any non-empty string, "false" included, as true
+        assert_eq!(form_value(json!(false)).as_deref(), Some("0"));
+        assert_eq!(form_value(json!(true)).as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_bulk_calls_carry_the_extra_params() {
+        let extra_params = vec![
+            ("tenant_key".to_string(), "k3y".to_string()),
+            ("idSite".to_string(), "9".to_string()),
+        ];
+        let params = HashMap::from([("idSite".to_string(), json!(1))]);
+        assert_eq!(
+            bulk_query("VisitsSummary", "get", params, &extra_params),
+            "method=VisitsSummary.get&idSite=1&tenant_key=k3y"
+        );
+    }
+
+    #[test]
+    fn test_trace_never_shows_the_token() {
+        let form = vec![
+            ("method".to_string(), "API.getMatomoVersion".to_string()),
+            ("tenant_key".to_string(), "k3y".to_string()),
+            ("token_auth".to_string(), "secret".to_string()),
+        ];
+        let extra_params = vec![("tenant_key".to_string(), "k3y".to_string())];
+        assert_eq!(
+            redacted_form(&form, &extra_params),
+            "method=API.getMatomoVersion&tenant_key=[redacted]&token_auth=[redacted]"
+        );
+        assert_eq!(truncate_bytes("héllo", 2), "h");
+        assert_eq!(truncate_bytes("hello", 10), "hello");
     }
 }
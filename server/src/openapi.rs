@@ -1,6 +1,7 @@
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 
 /// OpenAPI 3.0 specification (subset for our needs)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,7 +42,30 @@ pub struct PathItem {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Operation {
+    /// Tool name; a missing id is reported by `OpenApiSpec::validate`
+    #[serde(default)]
     pub operation_id: String,
+    /// Matomo method called by the operation (e.g. "VisitsSummary.get")
+    #[serde(
+        rename = "x-matomo-method",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub matomo_method: Option<String>,
+    /// Matomo module, kept verbatim as module names may contain underscores
+    #[serde(
+        rename = "x-matomo-module",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub matomo_module: Option<String>,
+    /// Matomo action, kept verbatim as action names may contain underscores
+    #[serde(
+        rename = "x-matomo-action",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub matomo_action: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub summary: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -51,6 +75,15 @@ pub struct Operation {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parameters: Option<Vec<Parameter>>,
     pub responses: IndexMap<String, Response>,
+    /// Unit of each metric returned by the operation (e.g. "seconds")
+    #[serde(
+        rename = "x-matomo-metric-units",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub metric_units: Option<BTreeMap<String, String>>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deprecated: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,15 +93,23 @@ pub struct Parameter {
     pub location: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    /// Optional when missing, as in OpenAPI
+    #[serde(default)]
     pub required: bool,
     pub schema: ParameterSchema,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub example: Option<serde_json::Value>,
+    /// `false` for arrays sent as a single comma-separated value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explode: Option<bool>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deprecated: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParameterSchema {
-    #[serde(rename = "type")]
+    /// Empty when missing; such parameters are reported by `OpenApiSpec::validate`
+    #[serde(rename = "type", default)]
     pub schema_type: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub format: Option<String>,
@@ -77,13 +118,21 @@ pub struct ParameterSchema {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "enum")]
     pub enum_values: Option<Vec<String>>,
+    /// Schema of the items of an array parameter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<Box<ParameterSchema>>,
+    /// Inclusive bounds of a numeric parameter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Response {
     pub description: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub content: Option<HashMap<String, MediaType>>,
+    pub content: Option<BTreeMap<String, MediaType>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -93,12 +142,15 @@ pub struct MediaType {
     pub example: Option<serde_json::Value>,
 }
 
+/// Prefix of references to shared schemas
+pub const COMPONENT_SCHEMAS_REF: &str = "#/components/schemas/";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Components {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub schemas: Option<HashMap<String, serde_json::Value>>,
+    pub schemas: Option<BTreeMap<String, serde_json::Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub security_schemes: Option<HashMap<String, SecurityScheme>>,
+    pub security_schemes: Option<BTreeMap<String, SecurityScheme>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -131,6 +183,11 @@ pub struct MatomoTool {
     pub action: String,
     pub description: String,
     pub parameters: Vec<ToolParameter>,
+    /// Unit of each metric returned by the tool, when known
+    pub metric_units: BTreeMap<String, String>,
+    /// JSON schema of a successful response, when the spec has one
+    pub response_schema: Option<serde_json::Value>,
+    pub deprecated: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -141,6 +198,14 @@ pub struct ToolParameter {
     pub param_type: String,
     pub default: Option<serde_json::Value>,
     pub enum_values: Option<Vec<String>>,
+    /// Item type of array parameters (e.g. "string")
+    pub item_type: Option<String>,
+    /// Example value from the spec, e.g. `deviceType==desktop` for `segment`
+    pub example: Option<serde_json::Value>,
+    /// Inclusive bounds of a numeric parameter, e.g. -1 for `filter_limit`
+    pub minimum: Option<i64>,
+    pub maximum: Option<i64>,
+    pub deprecated: bool,
 }
 
 impl OpenApiSpec {
@@ -151,6 +216,22 @@ impl OpenApiSpec {
         Ok(spec)
     }
 
+    /// Built-in subset of common reports (VisitsSummary, Actions, Referrers...)
+    ///
+    /// Compiled into the binary for `--offline`, which lists tools without
+    /// any Matomo connection.
+    pub fn offline() -> Self {
+        serde_json::from_str(include_str!("../offline-api.json"))
+            .expect("bundled offline-api.json is a valid spec")
+    }
+
+    /// Write the spec as pretty-printed JSON
+    pub fn to_file(&self, path: &str) -> anyhow::Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
     /// Maximum tool name length allowed by Claude.
     /// Claude rejects MCP tools whose name is 64 characters or longer.
     pub const MAX_TOOL_NAME_LENGTH: usize = 64;
@@ -159,7 +240,7 @@ impl OpenApiSpec {
     pub fn extract_tools(&self) -> Vec<MatomoTool> {
         let mut tools = Vec::new();
 
-        for (_path, path_item) in &self.paths {
+        for (path, path_item) in &self.paths {
             // Get the operation (prefer GET, fallback to POST)
             let operation = path_item.get.as_ref().or(path_item.post.as_ref());
 
@@ -174,13 +255,9 @@ impl OpenApiSpec {
                     Self::MAX_TOOL_NAME_LENGTH
                 );
 
-                // Parse operation_id to get module and action
-                // Format: "Module_action" -> module="Module", action="action"
-                let parts: Vec<&str> = op.operation_id.splitn(2, '_').collect();
-                let (module, action) = if parts.len() == 2 {
-                    (parts[0].to_string(), parts[1].to_string())
-                } else {
-                    (op.operation_id.clone(), op.operation_id.clone())
+                let (module, action) = match operation_method(path, op) {
+                    Some((module, action)) => (module.to_string(), action.to_string()),
+                    None => (op.operation_id.clone(), op.operation_id.clone()),
                 };
 
                 // Build description
@@ -204,6 +281,15 @@ impl OpenApiSpec {
                                 param_type: p.schema.schema_type.clone(),
                                 default: p.schema.default.clone(),
                                 enum_values: p.schema.enum_values.clone(),
+                                item_type: p
+                                    .schema
+                                    .items
+                                    .as_ref()
+                                    .map(|items| items.schema_type.clone()),
+                                example: p.example.clone(),
+                                minimum: p.schema.minimum,
+                                maximum: p.schema.maximum,
+                                deprecated: p.deprecated,
                             })
                             .collect()
                     })
@@ -215,6 +301,14 @@ impl OpenApiSpec {
                     action,
                     description,
                     parameters,
+                    metric_units: op.metric_units.clone().unwrap_or_default(),
+                    response_schema: op
+                        .responses
+                        .get("200")
+                        .and_then(|r| r.content.as_ref())
+                        .and_then(|c| c.get("application/json"))
+                        .map(|m| self.resolve_schema_refs(&m.schema, 0)),
+                    deprecated: op.deprecated,
                 });
             }
         }
@@ -222,16 +316,368 @@ impl OpenApiSpec {
         tools
     }
 
+    /// Check that every operation can become a working tool
+    ///
+    /// Returns one warning per problem. Operations with a `skipped` warning
+    /// (no usable operation id, no module/action, a duplicate id, a parameter
+    /// without name or with an unknown type) would produce broken tools and
+    /// should be left out; the other warnings are informational.
+    pub fn validate(&self) -> Vec<SpecWarning> {
+        let mut warnings = Vec::new();
+        let mut operation_ids = HashSet::new();
+
+        for (path, path_item) in &self.paths {
+            let mut warn = |message: String, skipped: bool| {
+                warnings.push(SpecWarning {
+                    path: path.clone(),
+                    message,
+                    skipped,
+                })
+            };
+            let Some(op) = path_item.get.as_ref().or(path_item.post.as_ref()) else {
+                warn("no GET or POST operation".to_string(), false);
+                continue;
+            };
+
+            let id = &op.operation_id;
+            if id.is_empty()
+                || id.len() >= Self::MAX_TOOL_NAME_LENGTH
+                || !id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                warn(
+                    format!(
+                        "operationId '{}' is not a valid tool name (1 to {} characters among \
+                         letters, digits, '_' and '-')",
+                        id,
+                        Self::MAX_TOOL_NAME_LENGTH - 1
+                    ),
+                    true,
+                );
+                continue;
+            }
+            if !operation_ids.insert(id.as_str()) {
+                warn(format!("duplicate operationId '{}'", id), true);
+                continue;
+            }
+            let is_identifier =
+                |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !operation_method(path, op)
+                .is_some_and(|(module, action)| is_identifier(module) && is_identifier(action))
+            {
+                warn(
+                    format!(
+                        "no Matomo module and action for '{}' (set x-matomo-method to \
+                         \"Module.action\")",
+                        id
+                    ),
+                    true,
+                );
+                continue;
+            }
+
+            let mut names = HashSet::new();
+            for param in op.parameters.iter().flatten() {
+                let schema = &param.schema;
+                if param.name.trim().is_empty() {
+                    warn(format!("'{}' has a parameter without a name", id), true);
+                } else if schema.schema_type.is_empty() {
+                    warn(
+                        format!("parameter '{}' of '{}' has no type", param.name, id),
+                        true,
+                    );
+                } else if !PARAMETER_TYPES.contains(&schema.schema_type.as_str()) {
+                    warn(
+                        format!(
+                            "parameter '{}' of '{}' has the unknown type '{}'",
+                            param.name, id, schema.schema_type
+                        ),
+                        true,
+                    );
+                } else if !names.insert(param.name.as_str()) {
+                    warn(
+                        format!("parameter '{}' of '{}' is declared twice", param.name, id),
+                        false,
+                    );
+                } else if matches!((schema.minimum, schema.maximum), (Some(min), Some(max)) if min > max)
+                {
+                    warn(
+                        format!(
+                            "parameter '{}' of '{}' has a minimum above its maximum",
+                            param.name, id
+                        ),
+                        false,
+                    );
+                }
+            }
+        }
+
+        warnings
+    }
+
+    /// Drop the operations `validate` skips, returning all its warnings
+    pub fn remove_unusable_operations(&mut self) -> Vec<SpecWarning> {
+        let warnings = self.validate();
+        let skipped: HashSet<&str> = warnings
+            .iter()
+            .filter(|warning| warning.skipped)
+            .map(|warning| warning.path.as_str())
+            .collect();
+        self.paths
+            .retain(|path, _| !skipped.contains(path.as_str()));
+        warnings
+    }
+
+    /// Inline `#/components/schemas/...` references of a schema
+    ///
+    /// Unknown references are left as-is; `depth` bounds recursive schemas.
+    fn resolve_schema_refs(&self, schema: &serde_json::Value, depth: usize) -> serde_json::Value {
+        const MAX_DEPTH: usize = 16;
+
+        match schema {
+            serde_json::Value::Object(obj) => {
+                let target = obj
+                    .get("$ref")
+                    .and_then(|r| r.as_str())
+                    .and_then(|r| r.strip_prefix(COMPONENT_SCHEMAS_REF))
+                    .and_then(|name| self.components.as_ref()?.schemas.as_ref()?.get(name));
+                match target {
+                    Some(target) if depth < MAX_DEPTH => {
+                        self.resolve_schema_refs(target, depth + 1)
+                    }
+                    _ => serde_json::Value::Object(
+                        obj.iter()
+                            .map(|(k, v)| (k.clone(), self.resolve_schema_refs(v, depth)))
+                            .collect(),
+                    ),
+                }
+            }
+            serde_json::Value::Array(items) => serde_json::Value::Array(
+                items
+                    .iter()
+                    .map(|v| self.resolve_schema_refs(v, depth))
+                    .collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+
     /// Get the base URL from servers
     pub fn get_base_url(&self) -> Option<String> {
         self.servers.first().map(|s| s.url.clone())
     }
+
+    /// Get the URL of the server whose description matches `name` (case-insensitively)
+    ///
+    /// Fails with the list of available descriptions when no server matches.
+    pub fn get_base_url_by_description(&self, name: &str) -> anyhow::Result<String> {
+        self.servers
+            .iter()
+            .find(|s| {
+                s.description
+                    .as_deref()
+                    .is_some_and(|d| d.eq_ignore_ascii_case(name.trim()))
+            })
+            .map(|s| s.url.clone())
+            .ok_or_else(|| {
+                let available: Vec<&str> = self
+                    .servers
+                    .iter()
+                    .filter_map(|s| s.description.as_deref())
+                    .collect();
+                anyhow::anyhow!(
+                    "No server named '{}' in the OpenAPI spec. Available servers: {}",
+                    name,
+                    if available.is_empty() {
+                        "(none has a description)".to_string()
+                    } else {
+                        available.join(", ")
+                    }
+                )
+            })
+    }
+}
+
+/// JSON schema types a tool parameter can have
+const PARAMETER_TYPES: &[&str] = &["string", "integer", "number", "boolean", "array", "object"];
+
+/// Problem found by `OpenApiSpec::validate` in one operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecWarning {
+    /// Path of the operation in the spec
+    pub path: String,
+    pub message: String,
+    /// The operation cannot become a working tool
+    pub skipped: bool,
+}
+
+impl fmt::Display for SpecWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)?;
+        if self.skipped {
+            write!(f, " (operation skipped)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Module and action of an operation
+///
+/// They come from x-matomo-module/x-matomo-action, then x-matomo-method
+/// ("Module.action"), then the path; older specs only have the operation id
+/// ("Module_action"). `None` when no source has both.
+fn operation_method<'a>(path: &'a str, op: &'a Operation) -> Option<(&'a str, &'a str)> {
+    op.matomo_module
+        .as_deref()
+        .zip(op.matomo_action.as_deref())
+        .or_else(|| {
+            op.matomo_method
+                .as_deref()
+                .and_then(|method| method.split_once('.'))
+        })
+        .or_else(|| method_from_path(path))
+        .or_else(|| op.operation_id.split_once('_'))
+}
+
+/// Recover "Module" and "action" from a method path
+///
+/// Supports the synthetic `/index.php/{Module}/{action}` paths as well as the
+/// legacy `/index.php?module=API&method=Module.action&format=json` keys.
+fn method_from_path(path: &str) -> Option<(&str, &str)> {
+    if let Some(rest) = path.strip_prefix("/index.php/") {
+        return rest
+            .split_once('/')
+            .filter(|(module, action)| !module.is_empty() && !action.is_empty());
+    }
+
+    let (_, query) = path.split_once('?')?;
+    query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("method="))
+        .and_then(|method| method.split_once('.'))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_get_base_url_by_description() {
+        let mut spec = OpenApiSpec::from_file("../matomo-api.json").unwrap();
+        spec.servers = vec![
+            Server {
+                url: "https://staging.example.com".to_string(),
+                description: Some("Staging".to_string()),
+            },
+            Server {
+                url: "https://matomo.example.com".to_string(),
+                description: Some("Production".to_string()),
+            },
+        ];
+
+        assert_eq!(
+            spec.get_base_url_by_description("production").unwrap(),
+            "https://matomo.example.com"
+        );
+        let err = spec.get_base_url_by_description("dev").unwrap_err();
+        assert!(err.to_string().contains("Staging, Production"), "{}", err);
+    }
+
+    #[test]
+    fn test_offline_spec_has_common_reports() {
+        let tools = OpenApiSpec::offline().extract_tools();
+        for name in [
+            "VisitsSummary_get",
+            "Actions_getPageUrls",
+            "Referrers_getAll",
+        ] {
+            assert!(tools.iter().any(|t| t.name == name), "missing {}", name);
+        }
+    }
+
+    #[test]
+    fn test_bundled_specs_are_valid() {
+        let spec = OpenApiSpec::from_file("../matomo-api.json").unwrap();
+        assert_eq!(spec.validate(), Vec::new());
+        assert_eq!(OpenApiSpec::offline().validate(), Vec::new());
+    }
+
+    #[test]
+    fn test_validate_flags_unusable_operations() {
+        let op = |id: &str, params: serde_json::Value| serde_json::json!({"get": {"operationId": id, "parameters": params, "responses": {}}});
+        let param = |name: &str, schema: serde_json::Value| serde_json::json!({"name": name, "in": "query", "required": false, "schema": schema});
+        let spec: OpenApiSpec = serde_json::from_value(serde_json::json!({
+            "openapi": "3.0.3",
+            "info": {"title": "Test", "version": "1.0"},
+            "servers": [{"url": "http://localhost"}],
+            "paths": {
+                "/index.php/VisitsSummary/get": op("VisitsSummary_get", serde_json::json!([
+                    param("idSite", serde_json::json!({"type": "integer", "minimum": 5, "maximum": 1}))
+                ])),
+                "/index.php/VisitsSummary/getVisits": op("VisitsSummary_get", serde_json::json!([])),
+                "/missing-id": {"get": {"responses": {}}},
+                "/no-method": op("getEverything", serde_json::json!([])),
+                "/index.php/Actions/get": op("Actions_get", serde_json::json!([
+                    param("period", serde_json::json!({"type": "text"}))
+                ])),
+            }
+        }))
+        .unwrap();
+
+        let warnings = spec.validate();
+        let summary: Vec<(&str, bool)> = warnings
+            .iter()
+            .map(|w| (w.path.as_str(), w.skipped))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("/index.php/Actions/get", true),
+                ("/index.php/VisitsSummary/get", false),
+                ("/index.php/VisitsSummary/getVisits", true),
+                ("/missing-id", true),
+                ("/no-method", true),
+            ]
+        );
+        assert!(warnings[0].message.contains("unknown type 'text'"));
+        assert!(warnings[2].to_string().contains("duplicate operationId"));
+
+        let mut spec = spec;
+        assert_eq!(spec.remove_unusable_operations(), warnings);
+        let tools = spec.extract_tools();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].action, "get");
+    }
+
+    #[test]
+    fn test_parameter_without_type_is_skipped() {
+        let mut spec: OpenApiSpec = serde_json::from_str(
+            r#"{
+                "openapi": "3.0.3",
+                "info": {"title": "Test", "version": "1.0"},
+                "servers": [{"url": "http://localhost"}],
+                "paths": {
+                    "/index.php/VisitsSummary/get": {"get": {
+                        "operationId": "VisitsSummary_get",
+                        "parameters": [{"name": "idSite", "in": "query", "required": false, "schema": {}}],
+                        "responses": {}
+                    }},
+                    "/index.php/Actions/get": {"get": {"operationId": "Actions_get", "responses": {}}}
+                }
+            }"#,
+        )
+        .expect("a parameter without type must not fail the whole spec");
+
+        let warnings = spec.remove_unusable_operations();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].skipped);
+        assert!(warnings[0]
+            .message
+            .contains("parameter 'idSite' of 'VisitsSummary_get' has no type"));
+        assert_eq!(spec.extract_tools().len(), 1);
+    }
+
     #[test]
     fn test_all_tool_names_under_64_chars_from_spec_file() {
         let spec = OpenApiSpec::from_file("../matomo-api.json")
@@ -283,11 +729,16 @@ mod tests {
                     PathItem {
                         get: Some(Operation {
                             operation_id: long_name.clone(),
+                            matomo_method: None,
+                            matomo_module: None,
+                            matomo_action: None,
                             summary: None,
                             description: None,
                             tags: None,
                             parameters: None,
                             responses: IndexMap::new(),
+                            metric_units: None,
+                            deprecated: false,
                         }),
                         post: None,
                     },
@@ -330,11 +781,16 @@ mod tests {
                     PathItem {
                         get: Some(Operation {
                             operation_id: name_63.clone(),
+                            matomo_method: None,
+                            matomo_module: None,
+                            matomo_action: None,
                             summary: None,
                             description: None,
                             tags: None,
                             parameters: None,
                             responses: IndexMap::new(),
+                            metric_units: None,
+                            deprecated: false,
                         }),
                         post: None,
                     },
@@ -349,4 +805,63 @@ mod tests {
         assert_eq!(tools.len(), 1);
         assert_eq!(tools[0].name, name_63);
     }
+
+    #[test]
+    fn test_method_from_path() {
+        assert_eq!(
+            method_from_path("/index.php/Goals/get_conversions"),
+            Some(("Goals", "get_conversions"))
+        );
+        assert_eq!(
+            method_from_path("/index.php?module=API&method=VisitsSummary.get&format=json"),
+            Some(("VisitsSummary", "get"))
+        );
+        assert_eq!(method_from_path("/test"), None);
+    }
+
+    #[test]
+    fn test_underscored_names_survive_extraction() {
+        let operation = |operation_id: &str, module: Option<&str>, action: Option<&str>| PathItem {
+            get: Some(Operation {
+                operation_id: operation_id.to_string(),
+                matomo_method: None,
+                matomo_module: module.map(str::to_string),
+                matomo_action: action.map(str::to_string),
+                summary: None,
+                description: None,
+                tags: None,
+                parameters: None,
+                responses: IndexMap::new(),
+                metric_units: None,
+                deprecated: false,
+            }),
+            post: None,
+        };
+        let mut spec = OpenApiSpec::from_file("../matomo-api.json").unwrap();
+        spec.paths = IndexMap::from([
+            (
+                "/a".to_string(),
+                operation(
+                    "My_Plugin_get_something_weird",
+                    Some("My_Plugin"),
+                    Some("get_something_weird"),
+                ),
+            ),
+            // Legacy spec: only the operation id, split on the first underscore
+            (
+                "/b".to_string(),
+                operation("Goals_get_something_weird", None, None),
+            ),
+        ]);
+
+        let tools = spec.extract_tools();
+        assert_eq!(
+            (tools[0].module.as_str(), tools[0].action.as_str()),
+            ("My_Plugin", "get_something_weird")
+        );
+        assert_eq!(
+            (tools[1].module.as_str(), tools[1].action.as_str()),
+            ("Goals", "get_something_weird")
+        );
+    }
 }
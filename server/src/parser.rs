@@ -1,20 +1,37 @@
 use anyhow::Result;
 use regex::Regex;
 use scraper::{Html, Selector};
-use std::collections::HashMap;
-use tracing::{debug, info};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tracing::{info, warn};
 
 use crate::types::{MatomoParameter, MethodMetadata, MethodParameter, ParameterType};
 
 /// Parsed report method with documentation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ParsedReportMethod {
     pub module: String,
     pub action: String,
-    #[allow(dead_code)]
     pub name: String,
     pub documentation: Option<String>,
     pub category: Option<String>,
+    /// Display order of the report within its category
+    pub order: Option<i64>,
+    /// Report parameters declared in the metadata (e.g. `idGoal` for goal reports)
+    pub parameters: Vec<MethodParameter>,
+    /// Response formats the report supports, when the metadata lists them
+    pub supported_formats: Option<Vec<String>>,
+    /// Unit of each metric (e.g. `avg_time_on_site` -> "seconds"), from `metricTypes`
+    pub metric_units: BTreeMap<String, String>,
+    /// Whether the report has a dimension, i.e. returns one row per dimension value
+    pub has_dimension: bool,
+    /// Whether rows have subtables (`actionToLoadSubTables`), i.e. the report is hierarchical
+    pub has_subtables: bool,
+    /// Label of each metric (`metrics` and `processedMetrics`), e.g. `nb_visits` -> "Visits"
+    pub metrics: BTreeMap<String, String>,
+    /// Explanation of each metric, from `metricsDocumentation`
+    pub metrics_documentation: BTreeMap<String, String>,
+    /// Whether the metadata flags the report as deprecated
+    pub deprecated: bool,
 }
 
 /// Parse the method list response from Matomo API (getReportMetadata format)
@@ -43,6 +60,39 @@ pub fn parse_method_list(json: &serde_json::Value) -> Result<Vec<ParsedReportMet
                         .get("category")
                         .and_then(|v| v.as_str())
                         .map(|s| s.to_string());
+                    let order = obj.get("order").and_then(|v| v.as_i64());
+                    let parameters = obj
+                        .get("parameters")
+                        .map(parse_metadata_parameters)
+                        .unwrap_or_default();
+                    let supported_formats = obj.get("supportedFormats").and_then(|v| {
+                        let formats: HashSet<String> = v
+                            .as_array()?
+                            .iter()
+                            .filter_map(|f| f.as_str())
+                            .map(|f| f.to_uppercase())
+                            .collect();
+                        (!formats.is_empty()).then(|| sorted_values(formats))
+                    });
+                    let metric_units = obj
+                        .get("metricTypes")
+                        .map(parse_metric_units)
+                        .unwrap_or_default();
+                    let has_dimension = obj.get("dimension").is_some_and(|v| !v.is_null());
+                    let has_subtables = obj
+                        .get("actionToLoadSubTables")
+                        .is_some_and(|v| !v.is_null());
+                    let mut metrics = obj.get("metrics").map(parse_labels).unwrap_or_default();
+                    if let Some(processed) = obj.get("processedMetrics") {
+                        for (metric, label) in parse_labels(processed) {
+                            metrics.entry(metric).or_insert(label);
+                        }
+                    }
+                    let deprecated = is_deprecated(obj, documentation.as_deref());
+                    let metrics_documentation = obj
+                        .get("metricsDocumentation")
+                        .map(parse_labels)
+                        .unwrap_or_default();
 
                     if !module.is_empty() && !action.is_empty() {
                         methods.push(ParsedReportMethod {
@@ -51,6 +101,15 @@ pub fn parse_method_list(json: &serde_json::Value) -> Result<Vec<ParsedReportMet
                             name: name.to_string(),
                             documentation,
                             category,
+                            order,
+                            parameters,
+                            supported_formats,
+                            metric_units,
+                            has_dimension,
+                            has_subtables,
+                            metrics,
+                            metrics_documentation,
+                            deprecated,
                         });
                     }
                 }
@@ -68,6 +127,15 @@ pub fn parse_method_list(json: &serde_json::Value) -> Result<Vec<ParsedReportMet
                                 name: format!("{}.{}", module, action_name),
                                 documentation: None,
                                 category: None,
+                                order: None,
+                                parameters: Vec::new(),
+                                supported_formats: None,
+                                metric_units: BTreeMap::new(),
+                                has_dimension: false,
+                                has_subtables: false,
+                                metrics: BTreeMap::new(),
+                                metrics_documentation: BTreeMap::new(),
+                                deprecated: false,
                             });
                         }
                     }
@@ -77,74 +145,361 @@ pub fn parse_method_list(json: &serde_json::Value) -> Result<Vec<ParsedReportMet
         _ => anyhow::bail!("Unexpected method list format"),
     }
 
+    let methods = merge_report_variants(methods);
+
     info!("Parsed {} methods from API", methods.len());
     Ok(methods)
 }
 
+/// Parse the `parameters` object of a getReportMetadata entry
+///
+/// Matomo lists the fixed parameter values identifying a report variant,
+/// e.g. `{"idGoal": "1"}` for the report of a single goal.
+fn parse_metadata_parameters(value: &serde_json::Value) -> Vec<MethodParameter> {
+    let Some(obj) = value.as_object() else {
+        return Vec::new();
+    };
+
+    obj.iter()
+        .map(|(name, value)| MethodParameter {
+            name: name.clone(),
+            required: true,
+            default: None,
+            known_values: match value {
+                serde_json::Value::String(s) => vec![s.clone()],
+                serde_json::Value::Null => Vec::new(),
+                other => vec![other.to_string()],
+            },
+            deprecated: false,
+        })
+        .collect()
+}
+
+/// Parse the `metricTypes` object of a getReportMetadata entry into units
+///
+/// Types without a meaningful unit (plain numbers) are skipped, so the map may
+/// be empty even when Matomo lists metric types.
+fn parse_metric_units(value: &serde_json::Value) -> BTreeMap<String, String> {
+    let Some(obj) = value.as_object() else {
+        return BTreeMap::new();
+    };
+
+    obj.iter()
+        .filter_map(|(metric, metric_type)| {
+            let unit = metric_unit(metric_type.as_str()?)?;
+            Some((metric.clone(), unit.to_string()))
+        })
+        .collect()
+}
+
+/// Whether a getReportMetadata entry is deprecated
+///
+/// Matomo has no dedicated flag for every version: an explicit `deprecated`
+/// / `isDeprecated` field is used when present, the documentation otherwise.
+fn is_deprecated(
+    obj: &serde_json::Map<String, serde_json::Value>,
+    documentation: Option<&str>,
+) -> bool {
+    let flagged = ["deprecated", "isDeprecated"]
+        .iter()
+        .filter_map(|key| obj.get(*key))
+        .any(|v| v.as_bool().unwrap_or(false) || v.as_i64() == Some(1) || v == "1");
+    flagged || documentation.is_some_and(mentions_deprecated)
+}
+
+/// Whether a text announces a deprecation ("deprecated", "@deprecated")
+fn mentions_deprecated(text: &str) -> bool {
+    text.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|word| word == "deprecated")
+}
+
+/// Parse a `{"id": "text"}` object of report metadata (metric labels, docs)
+///
+/// Some plugins list bare metric ids instead; the id then doubles as label.
+fn parse_labels(value: &serde_json::Value) -> BTreeMap<String, String> {
+    match value {
+        serde_json::Value::Object(obj) => obj
+            .iter()
+            .filter_map(|(id, text)| Some((id.clone(), text.as_str()?.trim().to_string())))
+            .collect(),
+        serde_json::Value::Array(ids) => ids
+            .iter()
+            .filter_map(|id| id.as_str())
+            .map(|id| (id.to_string(), id.to_string()))
+            .collect(),
+        _ => BTreeMap::new(),
+    }
+}
+
+/// Human readable unit of a Matomo metric type
+fn metric_unit(metric_type: &str) -> Option<&'static str> {
+    match metric_type {
+        "duration_s" => Some("seconds"),
+        "duration_ms" => Some("milliseconds"),
+        "percent" => Some("percentage"),
+        "money" => Some("currency of the site"),
+        "byte" => Some("bytes"),
+        _ => None,
+    }
+}
+
+/// Merge report variants sharing the same module/action into a single method
+///
+/// getReportMetadata returns one entry per report variant (e.g. one `Goals.get`
+/// per goal plus the overview). A parameter is only required when every
+/// variant declares it. The values seen across variants are collected and
+/// sorted so that regenerating a spec yields the same ordering.
+fn merge_report_variants(methods: Vec<ParsedReportMethod>) -> Vec<ParsedReportMethod> {
+    /// Per-method bookkeeping while merging variants
+    struct Variants {
+        method: ParsedReportMethod,
+        count: usize,
+        param_counts: HashMap<String, usize>,
+        param_values: HashMap<String, HashSet<String>>,
+    }
+
+    let mut merged: Vec<Variants> = Vec::new();
+
+    for method in methods {
+        let index = match merged
+            .iter()
+            .position(|v| v.method.module == method.module && v.method.action == method.action)
+        {
+            Some(index) => {
+                let target = &mut merged[index].method;
+                if target.documentation.is_none() {
+                    target.documentation = method.documentation.clone();
+                }
+                if target.category.is_none() {
+                    target.category = method.category.clone();
+                }
+                target.order = target.order.or(method.order);
+                if target.supported_formats.is_none() {
+                    target.supported_formats = method.supported_formats.clone();
+                }
+                for (metric, unit) in &method.metric_units {
+                    target
+                        .metric_units
+                        .entry(metric.clone())
+                        .or_insert_with(|| unit.clone());
+                }
+                for (metric, label) in &method.metrics {
+                    target
+                        .metrics
+                        .entry(metric.clone())
+                        .or_insert_with(|| label.clone());
+                }
+                target.has_subtables |= method.has_subtables;
+                // A method stays available as long as one variant is not deprecated
+                target.deprecated &= method.deprecated;
+                for (metric, doc) in &method.metrics_documentation {
+                    target
+                        .metrics_documentation
+                        .entry(metric.clone())
+                        .or_insert_with(|| doc.clone());
+                }
+                for param in &method.parameters {
+                    if !target.parameters.iter().any(|p| p.name == param.name) {
+                        target.parameters.push(param.clone());
+                    }
+                }
+                index
+            }
+            None => {
+                merged.push(Variants {
+                    method: method.clone(),
+                    count: 0,
+                    param_counts: HashMap::new(),
+                    param_values: HashMap::new(),
+                });
+                merged.len() - 1
+            }
+        };
+
+        let variants = &mut merged[index];
+        variants.count += 1;
+        for param in &method.parameters {
+            *variants.param_counts.entry(param.name.clone()).or_insert(0) += 1;
+            variants
+                .param_values
+                .entry(param.name.clone())
+                .or_default()
+                .extend(param.known_values.iter().cloned());
+        }
+    }
+
+    merged
+        .into_iter()
+        .map(|mut variants| {
+            for param in &mut variants.method.parameters {
+                param.required =
+                    variants.param_counts.get(&param.name).copied() == Some(variants.count);
+                param.known_values = variants
+                    .param_values
+                    .remove(&param.name)
+                    .map(sorted_values)
+                    .unwrap_or_default();
+            }
+            variants.method
+        })
+        .collect()
+}
+
+/// Turn a collected set of values into a deterministically ordered list
+///
+/// Values collected from live data go through a `HashSet`, whose iteration
+/// order changes between runs; sorting avoids spec churn.
+pub fn sorted_values(values: HashSet<String>) -> Vec<String> {
+    let mut values: Vec<String> = values.into_iter().collect();
+    values.sort();
+    values
+}
+
 /// Parse the API reference HTML page to extract method metadata
+///
+/// The page comes from a lossy decoding and is cleaned first (see
+/// `clean_reference`). Methods are recovered by two passes, signature lines
+/// then method headings; how many each found is logged, with a warning when
+/// no parameter at all could be read.
 pub fn parse_api_reference(html: &str) -> Result<HashMap<String, MethodMetadata>> {
-    let document = Html::parse_document(html);
+    let html = clean_reference(html);
+    let document = Html::parse_document(&html);
     let mut methods = HashMap::new();
 
     // Selector for method headings
-    let method_selector = Selector::parse("h2, h3, .apiMethod, .method-name").ok();
+    let method_selector =
+        Selector::parse("h2, h3, .apiMethod, .method-name").expect("valid selector");
 
     // Try to parse from the raw text using regex patterns
-    let method_pattern = Regex::new(r"(?m)^(\w+)\.(\w+)\s*\(?([^)]*)\)?").ok();
-
-    if let Some(pattern) = method_pattern {
-        for cap in pattern.captures_iter(html) {
-            let module = cap
-                .get(1)
-                .map(|m| m.as_str().to_string())
-                .unwrap_or_default();
-            let action = cap
-                .get(2)
-                .map(|m| m.as_str().to_string())
-                .unwrap_or_default();
-            let params_str = cap.get(3).map(|m| m.as_str()).unwrap_or("");
+    let method_pattern =
+        Regex::new(r"(?m)^(\w+)\.(\w+)\s*\(?([^)]*)\)?(.*)$").expect("valid regex");
 
-            let method_name = format!("{}.{}", module, action);
-            let parameters = parse_parameters_from_signature(params_str);
+    for cap in method_pattern.captures_iter(&html) {
+        let module = cap
+            .get(1)
+            .map(|m| m.as_str().to_string())
+            .unwrap_or_default();
+        let action = cap
+            .get(2)
+            .map(|m| m.as_str().to_string())
+            .unwrap_or_default();
+        let params_str = cap.get(3).map(|m| m.as_str()).unwrap_or("");
+        let rest = cap.get(4).map(|m| m.as_str()).unwrap_or("");
 
-            methods.insert(
-                method_name,
-                MethodMetadata {
-                    parameters,
-                    example_url: None,
-                },
-            );
-        }
+        let method_name = format!("{}.{}", module, action);
+        let parameters = parse_parameters_from_signature(params_str);
+
+        methods.insert(
+            method_name,
+            MethodMetadata {
+                parameters,
+                example_url: None,
+                deprecated: mentions_deprecated(rest),
+            },
+        );
     }
 
+    let from_signatures = methods.len();
+
     // Try parsing the formatted HTML structure
-    if let Some(selector) = method_selector {
-        for element in document.select(&selector) {
-            let text = element.text().collect::<String>();
-            if let Some((module, action)) = text.trim().split_once('.') {
-                let method_name = format!("{}.{}", module.trim(), action.trim());
-
-                methods
-                    .entry(method_name)
-                    .or_insert_with(|| MethodMetadata {
-                        parameters: Vec::new(),
-                        example_url: None,
-                    });
+    for element in document.select(&method_selector) {
+        let text = element.text().collect::<String>();
+        // Unclosed elements run into the next ones: only their first line is a heading
+        let heading = text.trim().lines().next().unwrap_or_default();
+        if let Some((module, action)) = heading.split_once('.') {
+            let (module, action) = (module.trim(), action.trim());
+            if !is_identifier(module) || !is_identifier(action) {
+                continue;
             }
+            let method_name = format!("{}.{}", module, action);
+
+            methods
+                .entry(method_name)
+                .or_insert_with(|| MethodMetadata {
+                    parameters: Vec::new(),
+                    example_url: None,
+                    deprecated: false,
+                });
         }
     }
 
-    debug!("Parsed {} method metadata entries from HTML", methods.len());
+    info!(
+        "Parsed {} methods from the API reference: {} from signatures, {} more from headings",
+        methods.len(),
+        from_signatures,
+        methods.len() - from_signatures
+    );
+    if methods.values().all(|method| method.parameters.is_empty()) {
+        warn!(
+            "No method parameters could be read from the API reference; tools will only \
+             have the common parameters and sparse metadata"
+        );
+    }
     Ok(methods)
 }
 
+/// Undo the encoding quirks seen in `listAllAPI` pages
+///
+/// Drops NULs and replacement characters left by lossy decoding, turns CR
+/// line ends into LF (signature lines are matched per line) and decodes the
+/// entities found in default values.
+fn clean_reference(html: &str) -> String {
+    html.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .replace(['\0', '\u{FFFD}'], "")
+        .replace("&nbsp;", " ")
+        .replace("&#039;", "'")
+        .replace("&#39;", "'")
+        .replace("&quot;", "\"")
+}
+
+/// Minimal report methods for the read methods (`get*`) of the API reference
+///
+/// Used when `getReportMetadata` lists nothing, e.g. for a token that cannot
+/// view reports: tools then only have a name and the reference parameters.
+pub fn methods_from_api_reference(
+    metadata: &HashMap<String, MethodMetadata>,
+) -> Vec<ParsedReportMethod> {
+    let mut methods: Vec<ParsedReportMethod> = metadata
+        .iter()
+        .filter_map(|(name, metadata)| {
+            let (module, action) = name.split_once('.')?;
+            (is_identifier(module) && is_identifier(action) && action.starts_with("get")).then(
+                || ParsedReportMethod {
+                    module: module.to_string(),
+                    action: action.to_string(),
+                    name: name.clone(),
+                    deprecated: metadata.deprecated,
+                    ..Default::default()
+                },
+            )
+        })
+        .collect();
+    methods.sort_by(|a, b| a.name.cmp(&b.name));
+    methods
+}
+
+/// Whether a module or action name is a plain identifier
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 /// Parse parameters from a method signature string like "idSite, period, date, segment = ''"
 fn parse_parameters_from_signature(signature: &str) -> Vec<MethodParameter> {
     let mut params = Vec::new();
 
+    let is_name = |name: &str| {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+    };
+
     for part in signature.split(',') {
         let part = part.trim();
-        if part.is_empty() {
+        // Skip fragments of markup or broken text rather than inventing parameters
+        if !is_name(part.split('=').next().unwrap_or_default().trim()) {
             continue;
         }
 
@@ -159,12 +514,16 @@ fn parse_parameters_from_signature(signature: &str) -> Vec<MethodParameter> {
                         .trim_matches('"')
                         .to_string(),
                 ),
+                known_values: Vec::new(),
+                deprecated: is_deprecated_name(name),
             });
         } else {
             params.push(MethodParameter {
                 name: part.to_string(),
                 required: true,
                 default: None,
+                known_values: Vec::new(),
+                deprecated: is_deprecated_name(part),
             });
         }
     }
@@ -172,17 +531,71 @@ fn parse_parameters_from_signature(signature: &str) -> Vec<MethodParameter> {
     params
 }
 
+/// Matomo keeps retired arguments in signatures as `$deprecatedFoo` / `$_deprecated`
+fn is_deprecated_name(name: &str) -> bool {
+    name.to_lowercase().contains("deprecated")
+}
+
+/// Parameters taking a comma-separated list of values (lowercased names)
+const LIST_PARAMETERS: &[&str] = &["columns", "labels", "showcolumns", "hidecolumns"];
+
+/// Matomo id parameters with their type, checked before the name heuristics
+///
+/// Visitor ids are hexadecimal and Tag Manager container ids are strings,
+/// although their names look like integer ids.
+const ID_PARAMETERS: &[(&str, ParameterType)] = &[
+    ("idSite", ParameterType::Integer),
+    ("idSites", ParameterType::Array),
+    ("idSubtable", ParameterType::Integer),
+    ("idGoal", ParameterType::Integer),
+    ("idDimension", ParameterType::Integer),
+    ("idSegment", ParameterType::Integer),
+    ("idAlert", ParameterType::Integer),
+    ("idReport", ParameterType::Integer),
+    ("idVisit", ParameterType::Integer),
+    ("idVisitor", ParameterType::String),
+    ("visitorId", ParameterType::String),
+    ("idContainer", ParameterType::String),
+];
+
+/// Whether a name designates an id: `id`, `idFoo`, `id_foo`, `fooId` or `foo_id`
+fn is_id_name(name: &str) -> bool {
+    let after_prefix = name.strip_prefix("id").map(|rest| rest.chars().next());
+    matches!(after_prefix, Some(None | Some('_')))
+        || matches!(after_prefix, Some(Some(c)) if c.is_ascii_uppercase())
+        || name.ends_with("Id")
+        || name.ends_with("_id")
+}
+
+/// Item type of an array parameter: integers for id lists (`idSites`), strings otherwise
+pub fn infer_array_item_type(name: &str) -> ParameterType {
+    match name.strip_suffix('s') {
+        Some(singular) if is_id_name(singular) => ParameterType::Integer,
+        _ => ParameterType::String,
+    }
+}
+
 /// Infer parameter type from its name and default value
 pub fn infer_parameter_type(name: &str, default: Option<&str>) -> ParameterType {
+    if let Some((_, param_type)) = ID_PARAMETERS.iter().find(|(known, _)| *known == name) {
+        return param_type.clone();
+    }
+
     let name_lower = name.to_lowercase();
 
+    // Comma-separated lists (idGoals, segmentIds, columns, hideColumns, ...)
+    if name
+        .strip_suffix('s')
+        .is_some_and(|singular| is_id_name(singular) || singular.ends_with("ID"))
+        || LIST_PARAMETERS.contains(&name_lower.as_str())
+    {
+        return ParameterType::Array;
+    }
+
     // Check for common patterns in Matomo API
-    if name_lower.contains("id") && !name_lower.contains("ids") {
+    if is_id_name(name) {
         return ParameterType::Integer;
     }
-    if name_lower.ends_with("ids") {
-        return ParameterType::String; // Comma-separated IDs
-    }
     if name_lower.contains("date") || name_lower == "day" {
         return ParameterType::Date;
     }
@@ -241,7 +654,13 @@ pub fn convert_parameter(param: &MethodParameter) -> MatomoParameter {
         required: param.required,
         param_type,
         default_value: param.default.clone(),
-        description: None,
+        description: if param.known_values.is_empty() {
+            None
+        } else {
+            Some(format!("Known values: {}", param.known_values.join(", ")))
+        },
+        enum_values: None,
+        deprecated: param.deprecated,
     }
 }
 
@@ -254,6 +673,8 @@ pub fn get_common_parameters() -> Vec<MatomoParameter> {
             param_type: ParameterType::Integer,
             default_value: None,
             description: Some("The site ID".to_string()),
+            enum_values: None,
+            deprecated: false,
         },
         MatomoParameter {
             name: "period".to_string(),
@@ -261,6 +682,8 @@ pub fn get_common_parameters() -> Vec<MatomoParameter> {
             param_type: ParameterType::String,
             default_value: None,
             description: Some("The period (day, week, month, year, range)".to_string()),
+            enum_values: None,
+            deprecated: false,
         },
         MatomoParameter {
             name: "date".to_string(),
@@ -270,6 +693,8 @@ pub fn get_common_parameters() -> Vec<MatomoParameter> {
             description: Some(
                 "The date (YYYY-MM-DD or keywords like 'today', 'yesterday')".to_string(),
             ),
+            enum_values: None,
+            deprecated: false,
         },
         MatomoParameter {
             name: "segment".to_string(),
@@ -277,6 +702,8 @@ pub fn get_common_parameters() -> Vec<MatomoParameter> {
             param_type: ParameterType::String,
             default_value: None,
             description: Some("Segment definition".to_string()),
+            enum_values: None,
+            deprecated: false,
         },
         MatomoParameter {
             name: "format".to_string(),
@@ -284,13 +711,19 @@ pub fn get_common_parameters() -> Vec<MatomoParameter> {
             param_type: ParameterType::String,
             default_value: Some("JSON".to_string()),
             description: Some("Response format (JSON, XML, CSV, etc.)".to_string()),
+            enum_values: None,
+            deprecated: false,
         },
         MatomoParameter {
             name: "filter_limit".to_string(),
             required: false,
             param_type: ParameterType::Integer,
             default_value: None,
-            description: Some("Limit the number of rows returned".to_string()),
+            description: Some(
+                "Maximum number of rows returned; -1 returns every row (no limit)".to_string(),
+            ),
+            enum_values: None,
+            deprecated: false,
         },
         MatomoParameter {
             name: "filter_offset".to_string(),
@@ -298,6 +731,361 @@ pub fn get_common_parameters() -> Vec<MatomoParameter> {
             param_type: ParameterType::Integer,
             default_value: Some("0".to_string()),
             description: Some("Offset for pagination".to_string()),
+            enum_values: None,
+            deprecated: false,
+        },
+        MatomoParameter {
+            name: "filter_sort_column".to_string(),
+            required: false,
+            param_type: ParameterType::String,
+            default_value: None,
+            description: Some(
+                "Metric column to sort rows by (e.g. 'nb_visits', 'bounce_rate'). \
+                 Sorting happens before filter_limit, so the limit keeps the top rows"
+                    .to_string(),
+            ),
+            enum_values: None,
+            deprecated: false,
+        },
+        MatomoParameter {
+            name: "filter_sort_order".to_string(),
+            required: false,
+            param_type: ParameterType::String,
+            default_value: None,
+            description: Some(
+                "Sort direction of filter_sort_column: 'desc' (highest first) or 'asc'".to_string(),
+            ),
+            enum_values: Some(vec!["asc".to_string(), "desc".to_string()]),
+            deprecated: false,
+        },
+        MatomoParameter {
+            name: "filter_pattern".to_string(),
+            required: false,
+            param_type: ParameterType::String,
+            default_value: None,
+            description: Some(
+                "Only keep rows whose filter_column matches this regular expression \
+                 (e.g. '/blog' for page URLs containing /blog; escape special characters \
+                 like '.' or '?'). Applied by Matomo before filter_limit"
+                    .to_string(),
+            ),
+            enum_values: None,
+            deprecated: false,
+        },
+        MatomoParameter {
+            name: "filter_column".to_string(),
+            required: false,
+            param_type: ParameterType::String,
+            default_value: None,
+            description: Some(
+                "Column matched by filter_pattern; defaults to 'label' (the URL, \
+                 keyword, country... of each row)"
+                    .to_string(),
+            ),
+            enum_values: None,
+            deprecated: false,
+        },
+        MatomoParameter {
+            name: "language".to_string(),
+            required: false,
+            param_type: ParameterType::String,
+            default_value: None,
+            description: Some(
+                "Language code of the labels Matomo translates (e.g. 'fr', 'pt-br'): \
+                 report and category names, countries, browsers... Defaults to English"
+                    .to_string(),
+            ),
+            enum_values: None,
+            deprecated: false,
+        },
+        MatomoParameter {
+            name: "format_metrics".to_string(),
+            required: false,
+            param_type: ParameterType::Boolean,
+            default_value: None,
+            description: Some(
+                "Set to false for raw metric values (0.5, 72, 3.5) suited to arithmetic, \
+                 to true for display strings ('50%', '1 min 12s', '$3.50'). Without it \
+                 Matomo formats only some metrics, such as rates."
+                    .to_string(),
+            ),
+            enum_values: None,
+            deprecated: false,
+        },
+        MatomoParameter {
+            name: "filter_excludelowpop".to_string(),
+            required: false,
+            param_type: ParameterType::String,
+            default_value: None,
+            description: Some(
+                "Metric column used to exclude low-population rows (e.g. 'nb_visits'). \
+                 Use together with filter_excludelowpop_value"
+                    .to_string(),
+            ),
+            enum_values: None,
+            deprecated: false,
+        },
+        MatomoParameter {
+            name: "filter_excludelowpop_value".to_string(),
+            required: false,
+            param_type: ParameterType::Float,
+            default_value: None,
+            description: Some(
+                "Minimum value of the filter_excludelowpop column for a row to be kept \
+                 (e.g. 10 to only show rows with at least 10 visits). \
+                 Applied before filter_limit, so the limit counts only the remaining rows"
+                    .to_string(),
+            ),
+            enum_values: None,
+            deprecated: false,
+        },
+    ]
+}
+
+/// Options of hierarchical reports (rows with subtables, e.g. `Actions.getPageUrls`)
+pub fn get_hierarchical_parameters() -> Vec<MatomoParameter> {
+    vec![
+        MatomoParameter {
+            name: "flat".to_string(),
+            required: false,
+            param_type: ParameterType::Boolean,
+            default_value: None,
+            description: Some(
+                "Flatten the report into a single list: each row is labelled with its full path \
+                 (e.g. 'blog/2024/post') instead of having a subtable. Best for top-N lists \
+                 such as the most viewed pages"
+                    .to_string(),
+            ),
+            enum_values: None,
+            deprecated: false,
+        },
+        MatomoParameter {
+            name: "expanded".to_string(),
+            required: false,
+            param_type: ParameterType::Boolean,
+            default_value: None,
+            description: Some(
+                "Return the tree: each row embeds its child rows under 'subtable' instead of \
+                 only an idsubdatatable reference. Use to explore a hierarchy; the response \
+                 can be large"
+                    .to_string(),
+            ),
+            enum_values: None,
+            deprecated: false,
         },
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_method_list_reads_metadata_parameters() {
+        let json = serde_json::json!([
+            {
+                "module": "Goals",
+                "action": "get",
+                "name": "Goal 1",
+                "parameters": {"idGoal": "1"}
+            }
+        ]);
+
+        let methods = parse_method_list(&json).unwrap();
+        assert_eq!(methods.len(), 1);
+        assert_eq!(methods[0].parameters.len(), 1);
+        assert_eq!(methods[0].parameters[0].name, "idGoal");
+        assert!(methods[0].parameters[0].required);
+    }
+
+    #[test]
+    fn test_parse_method_list_reads_category_and_order() {
+        let json = serde_json::json!([
+            {"module": "Goals", "action": "get", "name": "Goal 1", "category": "Goals"},
+            {"module": "Goals", "action": "get", "name": "Goals", "category": "Goals", "order": 3}
+        ]);
+
+        let methods = merge_report_variants(parse_method_list(&json).unwrap());
+        assert_eq!(methods[0].category.as_deref(), Some("Goals"));
+        assert_eq!(methods[0].order, Some(3));
+    }
+
+    #[test]
+    fn test_parse_method_list_merges_report_variants() {
+        let json = serde_json::json!([
+            {"module": "Goals", "action": "get", "name": "Goals overview"},
+            {"module": "Goals", "action": "get", "name": "Goal 1", "parameters": {"idGoal": "1"}},
+            {"module": "Goals", "action": "get", "name": "Goal 2", "parameters": {"idGoal": "2"}},
+            {"module": "VisitsSummary", "action": "get", "name": "Visits Summary"}
+        ]);
+
+        let methods = parse_method_list(&json).unwrap();
+        assert_eq!(methods.len(), 2);
+        assert_eq!(methods[0].module, "Goals");
+        assert_eq!(methods[0].parameters.len(), 1);
+        assert_eq!(methods[0].parameters[0].name, "idGoal");
+        assert!(
+            !methods[0].parameters[0].required,
+            "idGoal is absent from the overview variant, so it must be optional"
+        );
+        assert!(methods[1].parameters.is_empty());
+    }
+
+    #[test]
+    fn test_parse_method_list_reads_metric_units() {
+        let json = serde_json::json!([
+            {
+                "module": "VisitsSummary",
+                "action": "get",
+                "name": "Visits Summary",
+                "metricTypes": {
+                    "nb_visits": "number",
+                    "avg_time_on_site": "duration_s",
+                    "bounce_rate": "percent"
+                }
+            },
+            {"module": "Referrers", "action": "getReferrerType", "dimension": "Channel Type"}
+        ]);
+
+        let methods = parse_method_list(&json).unwrap();
+        let summary = &methods[0];
+        assert!(!summary.has_dimension);
+        assert_eq!(summary.metric_units.len(), 2);
+        assert_eq!(summary.metric_units["avg_time_on_site"], "seconds");
+        assert_eq!(summary.metric_units["bounce_rate"], "percentage");
+        assert!(methods[1].has_dimension);
+        assert!(methods[1].metric_units.is_empty());
+    }
+
+    #[test]
+    fn test_parse_method_list_reads_metric_labels() {
+        let json = serde_json::json!([{
+            "module": "VisitsSummary",
+            "action": "get",
+            "metrics": {"nb_visits": "Visits"},
+            "processedMetrics": {"bounce_rate": "Bounce Rate"},
+            "metricsDocumentation": {"nb_visits": "Number of visits."}
+        }]);
+
+        let methods = parse_method_list(&json).unwrap();
+        assert_eq!(methods[0].metrics["nb_visits"], "Visits");
+        assert_eq!(methods[0].metrics["bounce_rate"], "Bounce Rate");
+        assert_eq!(
+            methods[0].metrics_documentation["nb_visits"],
+            "Number of visits."
+        );
+    }
+
+    #[test]
+    fn test_parse_method_list_reads_deprecation() {
+        let json = serde_json::json!([
+            {"module": "Old", "action": "get", "deprecated": true},
+            {"module": "Doc", "action": "get", "documentation": "@deprecated Use VisitsSummary.get"},
+            {"module": "Current", "action": "get", "documentation": "Visits overview"}
+        ]);
+
+        let methods = parse_method_list(&json).unwrap();
+        let deprecated: Vec<bool> = methods.iter().map(|m| m.deprecated).collect();
+        assert_eq!(deprecated, vec![true, true, false]);
+
+        let params = parse_parameters_from_signature("idSite, $deprecatedPeriod = ''");
+        assert!(!params[0].deprecated);
+        assert!(params[1].deprecated);
+    }
+
+    #[test]
+    fn test_parse_method_list_sorts_collected_values() {
+        let json = serde_json::json!([
+            {"module": "Goals", "action": "get", "parameters": {"idGoal": "ecommerceOrder"}},
+            {"module": "Goals", "action": "get", "parameters": {"idGoal": "2"}},
+            {"module": "Goals", "action": "get", "parameters": {"idGoal": "10"}},
+            {"module": "Goals", "action": "get", "parameters": {"idGoal": "2"}},
+            {"module": "Goals", "action": "get", "parameters": {"idGoal": "1"}}
+        ]);
+
+        let methods = parse_method_list(&json).unwrap();
+        assert_eq!(
+            methods[0].parameters[0].known_values,
+            vec!["1", "10", "2", "ecommerceOrder"]
+        );
+
+        let param = convert_parameter(&methods[0].parameters[0]);
+        assert_eq!(
+            param.description.as_deref(),
+            Some("Known values: 1, 10, 2, ecommerceOrder")
+        );
+    }
+
+    #[test]
+    fn test_methods_from_api_reference_keeps_read_methods() {
+        let metadata = parse_api_reference(
+            "VisitsSummary.get (idSite, period, date, segment = '')\n\
+             SitesManager.getAllSites ()\n\
+             SitesManager.deleteSite (idSite)\n",
+        )
+        .unwrap();
+
+        let methods = methods_from_api_reference(&metadata);
+        let names: Vec<&str> = methods.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["SitesManager.getAllSites", "VisitsSummary.get"]);
+        assert_eq!(methods[1].module, "VisitsSummary");
+        assert_eq!(methods[1].action, "get");
+    }
+
+    #[test]
+    fn test_parse_api_reference_recovers_from_messy_html() {
+        let html = "<html><body><h2>Actions.getPageUrls</h2>\r\n\
+                    Actions.getPageUrls (idSite, period,&nbsp;date, segment = &#039;&#039;, \u{FFFD}<b>, flat = \0'0')\r\n\
+                    <div class=\"apiMethod\">Goals.get\r\n\
+                    VisitsSummary.get (idSite, period, date)<p>unclosed";
+
+        let methods = parse_api_reference(html).unwrap();
+        let names: Vec<&str> = methods["Actions.getPageUrls"]
+            .parameters
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["idSite", "period", "date", "segment", "flat"]);
+        let flat = &methods["Actions.getPageUrls"].parameters[4];
+        assert_eq!(flat.default.as_deref(), Some("0"));
+        assert_eq!(methods["VisitsSummary.get"].parameters.len(), 3);
+        assert!(methods["Goals.get"].parameters.is_empty());
+        assert_eq!(methods.len(), 3);
+    }
+
+    #[test]
+    fn test_infer_list_parameters_as_arrays() {
+        assert_eq!(infer_parameter_type("idSites", None), ParameterType::Array);
+        assert_eq!(
+            infer_parameter_type("hideColumns", None),
+            ParameterType::Array
+        );
+        assert_eq!(infer_parameter_type("columns", None), ParameterType::Array);
+        assert_eq!(infer_parameter_type("idGoal", None), ParameterType::Integer);
+    }
+
+    #[test]
+    fn test_infer_id_parameters() {
+        for (name, expected) in [
+            ("idSite", ParameterType::Integer),
+            ("idSites", ParameterType::Array),
+            ("idSubtable", ParameterType::Integer),
+            ("idDimension", ParameterType::Integer),
+            ("idSegment", ParameterType::Integer),
+            ("idCustomReport", ParameterType::Integer),
+            ("segmentIds", ParameterType::Array),
+            ("visitorId", ParameterType::String),
+            ("idVisitor", ParameterType::String),
+            ("idContainer", ParameterType::String),
+            // Contain "id" without being ids
+            ("provider", ParameterType::String),
+            ("hideMetricsDoc", ParameterType::Boolean),
+        ] {
+            assert_eq!(infer_parameter_type(name, None), expected, "{}", name);
+        }
+
+        assert_eq!(infer_array_item_type("idSites"), ParameterType::Integer);
+        assert_eq!(infer_array_item_type("segmentIds"), ParameterType::Integer);
+        assert_eq!(infer_array_item_type("columns"), ParameterType::String);
+    }
+}
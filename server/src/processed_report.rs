@@ -0,0 +1,157 @@
+//! Reshaping of `API.getProcessedReport` responses
+//!
+//! A processed report bundles the report metadata, the display name of each
+//! column, the rows keyed by metric id and the totals. Models read it more
+//! reliably as a table: the columns with their names, the rows as arrays
+//! aligned with them, and the totals keyed by display name.
+
+use serde_json::{json, Map, Value};
+
+/// Column of the row dimension (page URL, country...), listed first
+const LABEL_COLUMN: &str = "label";
+
+/// Column added to the rows of multi-period reports
+const DATE_COLUMN: &str = "date";
+
+/// Reshape a processed report into `{report, date, columns, rows, totals}`
+///
+/// `columns` lists `{id, name}` entries, the label first; each row holds one
+/// value per column (null when absent). Reports over several periods key
+/// their data by date: their rows start with a `date` column. Returns `None`
+/// when the response has no `columns` or `reportData`.
+pub fn reshape_processed_report(report: &Value) -> Option<Value> {
+    let names = report.get("columns")?.as_object()?;
+    let data = report.get("reportData")?;
+
+    let mut ids: Vec<&str> = names.keys().map(String::as_str).collect();
+    ids.sort_by_key(|id| *id != LABEL_COLUMN);
+
+    let row = |row: &Value| -> Vec<Value> {
+        ids.iter()
+            .map(|id| row.get(*id).cloned().unwrap_or(Value::Null))
+            .collect()
+    };
+    let mut columns: Vec<Value> = ids
+        .iter()
+        .map(|id| json!({"id": id, "name": names[*id]}))
+        .collect();
+
+    let rows: Vec<Value> = match data {
+        Value::Array(rows) => rows.iter().map(|r| Value::from(row(r))).collect(),
+        Value::Object(periods) if is_multi_period(periods) => {
+            columns.insert(0, json!({"id": DATE_COLUMN, "name": "Date"}));
+            periods
+                .iter()
+                .flat_map(|(date, data)| {
+                    let rows = match data {
+                        Value::Array(rows) => rows.iter().collect(),
+                        Value::Object(obj) if obj.is_empty() => Vec::new(),
+                        single => vec![single],
+                    };
+                    rows.into_iter().map(move |r| {
+                        let mut cells = vec![Value::from(date.as_str())];
+                        cells.extend(row(r));
+                        Value::from(cells)
+                    })
+                })
+                .collect()
+        }
+        // Reports without a dimension (e.g. VisitsSummary.get) have a single row
+        Value::Object(single) if !single.is_empty() => vec![Value::from(row(data))],
+        _ => Vec::new(),
+    };
+
+    let totals: Map<String, Value> = report
+        .get("reportTotal")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .map(|(id, value)| {
+            let name = names.get(id).and_then(Value::as_str).unwrap_or(id);
+            (name.to_string(), value.clone())
+        })
+        .collect();
+
+    Some(json!({
+        "report": report.pointer("/metadata/name"),
+        "date": report.get("prettyDate"),
+        "columns": columns,
+        "rows": rows,
+        "totals": totals,
+    }))
+}
+
+/// Whether `reportData` is keyed by period (every value is a row list or row)
+fn is_multi_period(data: &Map<String, Value>) -> bool {
+    !data.is_empty() && data.values().all(|v| v.is_array() || v.is_object())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Value {
+        json!({
+            "website": "example.com",
+            "prettyDate": "Monday, January 6, 2025",
+            "metadata": {"name": "Page URLs", "module": "Actions", "action": "getPageUrls"},
+            "columns": {
+                "nb_visits": "Unique Pageviews",
+                "label": "Page URL",
+                "nb_hits": "Pageviews"
+            },
+            "reportData": [
+                {"label": "/blog", "nb_visits": 12, "nb_hits": 20},
+                {"label": "/pricing", "nb_hits": 4}
+            ],
+            "reportMetadata": [{"url": "https://example.com/blog"}, {}],
+            "reportTotal": {"nb_visits": 12, "nb_hits": 24}
+        })
+    }
+
+    #[test]
+    fn test_rows_are_aligned_with_labeled_columns() {
+        let table = reshape_processed_report(&sample()).unwrap();
+        assert_eq!(table["report"], "Page URLs");
+        assert_eq!(table["date"], "Monday, January 6, 2025");
+        assert_eq!(
+            table["columns"],
+            json!([
+                {"id": "label", "name": "Page URL"},
+                {"id": "nb_hits", "name": "Pageviews"},
+                {"id": "nb_visits", "name": "Unique Pageviews"}
+            ])
+        );
+        assert_eq!(
+            table["rows"],
+            json!([["/blog", 20, 12], ["/pricing", 4, null]])
+        );
+        assert_eq!(
+            table["totals"],
+            json!({"Pageviews": 24, "Unique Pageviews": 12})
+        );
+    }
+
+    #[test]
+    fn test_multi_period_rows_start_with_the_date() {
+        let mut report = sample();
+        report["reportData"] = json!({
+            "2025-01-05": [{"label": "/blog", "nb_visits": 3, "nb_hits": 5}],
+            "2025-01-06": []
+        });
+        let table = reshape_processed_report(&report).unwrap();
+        assert_eq!(table["columns"][0]["id"], "date");
+        assert_eq!(table["rows"], json!([["2025-01-05", "/blog", 5, 3]]));
+    }
+
+    #[test]
+    fn test_single_row_report() {
+        let report = json!({
+            "columns": {"nb_visits": "Visits"},
+            "reportData": {"nb_visits": 42},
+        });
+        let table = reshape_processed_report(&report).unwrap();
+        assert_eq!(table["rows"], json!([[42]]));
+        assert!(reshape_processed_report(&json!({"result": "success"})).is_none());
+    }
+}
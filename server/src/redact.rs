@@ -0,0 +1,195 @@
+//! Redaction of personally identifiable information in Matomo responses
+//!
+//! Live reports expose visitor IPs, visitor IDs and precise locations. The
+//! redactor walks a response recursively and replaces the values of matching
+//! keys with a placeholder, keeping the structure intact. It can also mask
+//! string values that look like an email or IP address, whatever their key.
+
+use std::net::IpAddr;
+
+use crate::schema_inference::is_email;
+
+/// Placeholder written in place of redacted values
+pub const REDACTED: &str = "[redacted]";
+
+/// Keys masked by default (a trailing `*` matches any suffix)
+pub const DEFAULT_PII_KEYS: &[&str] = &[
+    "visitIp",
+    "visitorId",
+    "userId",
+    "email",
+    "latitude",
+    "longitude",
+    "location_*",
+];
+
+/// Which tool responses get PII masking
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PiiScope {
+    /// Only tools of the Live module (default)
+    LiveOnly,
+    /// Every tool response
+    All,
+    /// No masking
+    Off,
+}
+
+impl PiiScope {
+    /// Whether responses of the given module should be masked
+    pub fn applies_to(&self, module: &str) -> bool {
+        match self {
+            PiiScope::LiveOnly => module == "Live",
+            PiiScope::All => true,
+            PiiScope::Off => false,
+        }
+    }
+}
+
+/// Recursively masks values of configured keys
+#[derive(Debug, Clone)]
+pub struct PiiRedactor {
+    keys: Vec<String>,
+    /// Also mask strings that look like an email or IP address
+    detect_values: bool,
+}
+
+impl Default for PiiRedactor {
+    fn default() -> Self {
+        Self::new(DEFAULT_PII_KEYS.iter().map(|k| k.to_string()).collect())
+    }
+}
+
+impl PiiRedactor {
+    /// Create a redactor for the given key patterns
+    pub fn new(keys: Vec<String>) -> Self {
+        Self {
+            keys,
+            detect_values: false,
+        }
+    }
+
+    /// Mask email and IP address values under any key, too
+    pub fn with_value_detection(mut self) -> Self {
+        self.detect_values = true;
+        self
+    }
+
+    /// Whether a key matches one of the configured patterns
+    fn matches(&self, key: &str) -> bool {
+        self.keys
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => key.starts_with(prefix),
+                None => key == pattern,
+            })
+    }
+
+    /// Redact matching keys in place, recursing into objects and arrays
+    pub fn redact(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(obj) => {
+                for (key, val) in obj.iter_mut() {
+                    if self.matches(key) {
+                        if !val.is_null() {
+                            *val = serde_json::Value::String(REDACTED.to_string());
+                        }
+                    } else {
+                        self.redact(val);
+                    }
+                }
+            }
+            serde_json::Value::Array(arr) => {
+                for item in arr {
+                    self.redact(item);
+                }
+            }
+            serde_json::Value::String(s) if self.detect_values && looks_like_pii(s) => {
+                *value = serde_json::Value::String(REDACTED.to_string());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether a string value is an email or IP address
+fn looks_like_pii(s: &str) -> bool {
+    let s = s.trim();
+    is_email(s) || s.parse::<IpAddr>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact_nested_live_visits() {
+        let mut value = json!([
+            {
+                "idVisit": 1,
+                "visitIp": "192.168.1.1",
+                "visitorId": "abcdef0123456789",
+                "actionDetails": [{"url": "https://example.com", "email": "a@b.c"}],
+                "location_city": "Paris",
+                "location_geo": {"lat": 48.8}
+            }
+        ]);
+
+        PiiRedactor::default().redact(&mut value);
+
+        assert_eq!(value[0]["idVisit"], 1);
+        assert_eq!(value[0]["visitIp"], REDACTED);
+        assert_eq!(value[0]["visitorId"], REDACTED);
+        assert_eq!(value[0]["actionDetails"][0]["email"], REDACTED);
+        assert_eq!(value[0]["actionDetails"][0]["url"], "https://example.com");
+        assert_eq!(value[0]["location_city"], REDACTED);
+        assert_eq!(value[0]["location_geo"], REDACTED);
+    }
+
+    #[test]
+    fn test_redact_keeps_null_values() {
+        let mut value = json!({"userId": null});
+        PiiRedactor::default().redact(&mut value);
+        assert_eq!(value["userId"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_value_detection_masks_emails_and_ips_under_any_key() {
+        let mut value = json!({
+            "label": "jane@example.com",
+            "rows": [{"label": "10.0.0.0"}, {"label": "2001:db8::1"}, {"label": "/blog"}],
+            "nb_visits": 3,
+            "ref": "x"
+        });
+        PiiRedactor::new(vec!["ref".to_string()])
+            .with_value_detection()
+            .redact(&mut value);
+
+        assert_eq!(value["label"], REDACTED);
+        assert_eq!(value["rows"][0]["label"], REDACTED);
+        assert_eq!(value["rows"][1]["label"], REDACTED);
+        assert_eq!(value["rows"][2]["label"], "/blog");
+        assert_eq!(value["nb_visits"], 3);
+        assert_eq!(value["ref"], REDACTED);
+
+        let mut plain = json!({"label": "jane@example.com"});
+        PiiRedactor::new(Vec::new()).redact(&mut plain);
+        assert_eq!(plain["label"], "jane@example.com");
+    }
+
+    #[test]
+    fn test_custom_keys() {
+        let mut value = json!({"visitIp": "1.2.3.4", "secret": "x"});
+        PiiRedactor::new(vec!["secret".to_string()]).redact(&mut value);
+        assert_eq!(value["visitIp"], "1.2.3.4");
+        assert_eq!(value["secret"], REDACTED);
+    }
+
+    #[test]
+    fn test_scope() {
+        assert!(PiiScope::LiveOnly.applies_to("Live"));
+        assert!(!PiiScope::LiveOnly.applies_to("VisitsSummary"));
+        assert!(PiiScope::All.applies_to("VisitsSummary"));
+        assert!(!PiiScope::Off.applies_to("Live"));
+    }
+}
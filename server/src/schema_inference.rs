@@ -3,13 +3,151 @@
 //! This module is used when `--fetch-examples` is enabled to infer
 //! JSON schemas from example responses. Currently unused in the server
 //! but kept for future enhancements.
+//!
+//! Note that `required` lists the keys present in every analyzed example,
+//! while `properties` lists every key seen in at least one. Objects keyed by
+//! date (multi-period responses such as `date=last7`) are described with
+//! `additionalProperties` instead of one property per date.
 
 #![allow(dead_code)]
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 use crate::types::JsonSchema;
 
+/// Maximum number of distinct values for a string field to be treated as an enum
+pub const MAX_ENUM_VALUES: usize = 10;
+
+/// Minimum number of samples of a string field before it can be treated as an enum
+pub const MIN_ENUM_SAMPLES: usize = 2;
+
+/// Marker used in field paths for array items
+const ITEMS: &str = "[]";
+
+/// Marker used in field paths for the values of date-keyed objects
+const DATE_VALUES: &str = "{}";
+
+/// Infer a single schema from several example responses of the same method
+///
+/// Object properties are the union of the keys seen across examples, while
+/// `required` only lists the keys present in every example. Low-cardinality
+/// string fields (e.g. `referer_type`) get their observed values as `enum`.
+pub fn analyze_responses(responses: &[serde_json::Value]) -> JsonSchema {
+    let schemas: Vec<JsonSchema> = responses.iter().map(infer_schema).collect();
+    let mut schema = merge_schemas(&schemas);
+
+    let mut samples = HashMap::new();
+    for response in responses {
+        collect_string_samples(response, &mut Vec::new(), &mut samples);
+    }
+    apply_enums(&mut schema, &mut Vec::new(), &samples);
+
+    schema
+}
+
+/// Values seen for a field across all examples
+#[derive(Default)]
+struct FieldSamples {
+    count: usize,
+    values: BTreeSet<String>,
+    /// Whether a non-string value was seen, disqualifying the field
+    mixed: bool,
+}
+
+/// Record the values of every object field, keyed by path
+fn collect_string_samples(
+    value: &serde_json::Value,
+    path: &mut Vec<String>,
+    samples: &mut HashMap<Vec<String>, FieldSamples>,
+) {
+    match value {
+        serde_json::Value::Object(obj) => {
+            let date_keyed = is_date_keyed(obj);
+            for (key, val) in obj {
+                path.push(if date_keyed {
+                    DATE_VALUES.to_string()
+                } else {
+                    key.clone()
+                });
+                let field = samples.entry(path.clone()).or_default();
+                match val {
+                    serde_json::Value::String(s) => {
+                        field.count += 1;
+                        field.values.insert(s.clone());
+                    }
+                    serde_json::Value::Null => {}
+                    _ => field.mixed = true,
+                }
+                collect_string_samples(val, path, samples);
+                path.pop();
+            }
+        }
+        serde_json::Value::Array(arr) => {
+            path.push(ITEMS.to_string());
+            for item in arr {
+                collect_string_samples(item, path, samples);
+            }
+            path.pop();
+        }
+        _ => {}
+    }
+}
+
+/// Set `enum` on plain string properties with few distinct values
+fn apply_enums(
+    schema: &mut JsonSchema,
+    path: &mut Vec<String>,
+    samples: &HashMap<Vec<String>, FieldSamples>,
+) {
+    if let Some(items) = schema.items.as_mut() {
+        path.push(ITEMS.to_string());
+        apply_enums(items, path, samples);
+        path.pop();
+    }
+
+    if let Some(values) = schema.additional_properties.as_mut() {
+        path.push(DATE_VALUES.to_string());
+        apply_enums(values, path, samples);
+        path.pop();
+    }
+
+    let Some(props) = schema.properties.as_mut() else {
+        return;
+    };
+
+    for (key, prop) in props.iter_mut() {
+        path.push(key.clone());
+        if let Some(field) = samples.get(path.as_slice()) {
+            if is_enum_like(prop, field) {
+                prop.enum_values = Some(
+                    field
+                        .values
+                        .iter()
+                        .map(|v| serde_json::Value::String(v.clone()))
+                        .collect(),
+                );
+            }
+        }
+        apply_enums(prop, path, samples);
+        path.pop();
+    }
+}
+
+/// Whether a field looks like a fixed set of labels rather than free text
+///
+/// Strings with a detected format (dates, URLs) and numeric strings are
+/// never treated as enums.
+fn is_enum_like(schema: &JsonSchema, field: &FieldSamples) -> bool {
+    schema.schema_type == "string"
+        && schema.format.is_none()
+        && schema.description.is_none()
+        && !field.mixed
+        && field.count >= MIN_ENUM_SAMPLES
+        && !field.values.is_empty()
+        && field.values.len() <= MAX_ENUM_VALUES
+        && field.values.iter().all(|v| v.parse::<f64>().is_err())
+}
+
 /// Infer a JSON schema from a JSON value
 pub fn infer_schema(value: &serde_json::Value) -> JsonSchema {
     match value {
@@ -65,14 +203,29 @@ pub fn infer_schema(value: &serde_json::Value) -> JsonSchema {
             }
         }
 
+        // One entry per period: describe the value shape, not every date
+        serde_json::Value::Object(obj) if is_date_keyed(obj) => {
+            let value_schemas: Vec<JsonSchema> = obj.values().map(infer_schema).collect();
+            JsonSchema {
+                schema_type: "object".to_string(),
+                description: Some("Keyed by date".to_string()),
+                additional_properties: Some(Box::new(merge_schemas(&value_schemas))),
+                ..Default::default()
+            }
+        }
+
         serde_json::Value::Object(obj) => {
-            let mut properties = HashMap::new();
+            let mut properties = BTreeMap::new();
 
             for (key, val) in obj {
                 let prop_schema = infer_schema(val);
                 properties.insert(key.clone(), prop_schema);
             }
 
+            // Every key of a single example is present by definition
+            let mut required: Vec<String> = obj.keys().cloned().collect();
+            required.sort();
+
             JsonSchema {
                 schema_type: "object".to_string(),
                 properties: if properties.is_empty() {
@@ -80,7 +233,11 @@ pub fn infer_schema(value: &serde_json::Value) -> JsonSchema {
                 } else {
                     Some(properties)
                 },
-                required: None,
+                required: if required.is_empty() {
+                    None
+                } else {
+                    Some(required)
+                },
                 ..Default::default()
             }
         }
@@ -143,6 +300,18 @@ fn is_date(s: &str) -> bool {
     re.map(|r| r.is_match(s)).unwrap_or(false)
 }
 
+/// Check if every key of a non-empty object is a period date
+///
+/// Multi-period responses are keyed by `YYYY-MM-DD` (day), `YYYY-MM-DD,YYYY-MM-DD`
+/// (week, range), `YYYY-MM` (month) or `YYYY` (year).
+fn is_date_keyed(obj: &serde_json::Map<String, serde_json::Value>) -> bool {
+    let re = regex::Regex::new(r"^\d{4}(-\d{2}(-\d{2}(,\d{4}-\d{2}-\d{2})?)?)?$").ok();
+    match re {
+        Some(re) => !obj.is_empty() && obj.keys().all(|key| re.is_match(key)),
+        None => false,
+    }
+}
+
 /// Check if string looks like a datetime
 fn is_datetime(s: &str) -> bool {
     let re = regex::Regex::new(r"^\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}").ok();
@@ -155,7 +324,7 @@ fn is_url(s: &str) -> bool {
 }
 
 /// Check if string looks like an email
-fn is_email(s: &str) -> bool {
+pub fn is_email(s: &str) -> bool {
     s.contains('@') && s.contains('.')
 }
 
@@ -176,17 +345,45 @@ fn merge_schemas(schemas: &[JsonSchema]) -> JsonSchema {
     if all_same_type {
         match first_type.as_str() {
             "object" => {
-                // Merge object properties
-                let mut merged_props: HashMap<String, JsonSchema> = HashMap::new();
+                // Union of properties, merging schemas seen for the same key
+                let mut seen_props: HashMap<String, Vec<JsonSchema>> = HashMap::new();
 
                 for schema in schemas {
                     if let Some(props) = &schema.properties {
                         for (key, prop_schema) in props {
-                            merged_props.insert(key.clone(), prop_schema.clone());
+                            seen_props
+                                .entry(key.clone())
+                                .or_default()
+                                .push(prop_schema.clone());
                         }
                     }
                 }
 
+                let merged_props: BTreeMap<String, JsonSchema> = seen_props
+                    .into_iter()
+                    .map(|(key, prop_schemas)| (key, merge_schemas(&prop_schemas)))
+                    .collect();
+
+                // Intersection of required keys: only keys present in every object
+                let mut required: Option<BTreeSet<String>> = None;
+                for schema in schemas {
+                    let keys: BTreeSet<String> =
+                        schema.required.iter().flatten().cloned().collect();
+                    required = Some(match required {
+                        Some(acc) => acc.intersection(&keys).cloned().collect(),
+                        None => keys,
+                    });
+                }
+                let required: Vec<String> = required.unwrap_or_default().into_iter().collect();
+
+                // Date-keyed objects: merge the schemas of their values
+                let values: Vec<JsonSchema> = schemas
+                    .iter()
+                    .filter_map(|s| s.additional_properties.as_deref().cloned())
+                    .collect();
+                let additional_properties =
+                    (!values.is_empty()).then(|| Box::new(merge_schemas(&values)));
+
                 JsonSchema {
                     schema_type: "object".to_string(),
                     properties: if merged_props.is_empty() {
@@ -194,6 +391,28 @@ fn merge_schemas(schemas: &[JsonSchema]) -> JsonSchema {
                     } else {
                         Some(merged_props)
                     },
+                    required: if required.is_empty() {
+                        None
+                    } else {
+                        Some(required)
+                    },
+                    description: additional_properties
+                        .is_some()
+                        .then(|| "Keyed by date".to_string()),
+                    additional_properties,
+                    ..Default::default()
+                }
+            }
+            "array" => {
+                // Merge the item schemas instead of keeping the first array's
+                let items: Vec<JsonSchema> = schemas
+                    .iter()
+                    .filter_map(|s| s.items.as_deref().cloned())
+                    .collect();
+
+                JsonSchema {
+                    schema_type: "array".to_string(),
+                    items: (!items.is_empty()).then(|| Box::new(merge_schemas(&items))),
                     ..Default::default()
                 }
             }
@@ -208,3 +427,151 @@ fn merge_schemas(schemas: &[JsonSchema]) -> JsonSchema {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_single_object_requires_all_keys() {
+        let schema = infer_schema(&json!({"nb_visits": 3, "label": "Direct"}));
+        assert_eq!(
+            schema.required,
+            Some(vec!["label".to_string(), "nb_visits".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_required_is_intersection_of_keys() {
+        let schema = analyze_responses(&[
+            json!({"label": "a", "nb_visits": 1, "goals": {}}),
+            json!({"label": "b", "nb_visits": 2, "segment": "x"}),
+        ]);
+
+        let props = schema.properties.unwrap();
+        let mut names: Vec<&String> = props.keys().collect();
+        names.sort();
+        assert_eq!(names, ["goals", "label", "nb_visits", "segment"]);
+        assert_eq!(
+            schema.required,
+            Some(vec!["label".to_string(), "nb_visits".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_required_intersection_in_array_items() {
+        let schema = infer_schema(&json!([
+            {"label": "a", "nb_visits": 1},
+            {"label": "b"}
+        ]));
+        let items = schema.items.unwrap();
+        assert_eq!(items.required, Some(vec!["label".to_string()]));
+        assert_eq!(items.properties.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_no_common_keys_means_no_required() {
+        let schema = analyze_responses(&[json!({"a": 1}), json!({"b": 2})]);
+        assert_eq!(schema.required, None);
+    }
+
+    #[test]
+    fn test_low_cardinality_string_becomes_enum() {
+        let types = ["direct", "search", "website"];
+        let rows: Vec<serde_json::Value> = (0..30)
+            .map(|i| {
+                json!({
+                    "referer_type": types[i % 3],
+                    "label": format!("page-{}", i),
+                })
+            })
+            .collect();
+
+        let schema = analyze_responses(&[serde_json::Value::Array(rows)]);
+        let props = schema.items.unwrap().properties.unwrap();
+        assert_eq!(
+            props["referer_type"].enum_values,
+            Some(vec![json!("direct"), json!("search"), json!("website")])
+        );
+        // 30 distinct labels exceed MAX_ENUM_VALUES
+        assert_eq!(props["label"].enum_values, None);
+    }
+
+    #[test]
+    fn test_enum_requires_enough_samples() {
+        let schema = analyze_responses(&[json!({"deviceType": "Desktop"})]);
+        let props = schema.properties.unwrap();
+        assert_eq!(props["deviceType"].enum_values, None);
+
+        let schema = analyze_responses(&[
+            json!({"deviceType": "Desktop", "nb_visits": "12"}),
+            json!({"deviceType": "Smartphone", "nb_visits": "7"}),
+        ]);
+        let props = schema.properties.unwrap();
+        assert_eq!(
+            props["deviceType"].enum_values,
+            Some(vec![json!("Desktop"), json!("Smartphone")])
+        );
+        // Numeric strings are not enums
+        assert_eq!(props["nb_visits"].enum_values, None);
+    }
+
+    #[test]
+    fn test_date_keyed_object_uses_additional_properties() {
+        let schema = analyze_responses(&[json!({
+            "2024-01-01": {"nb_visits": 3, "referer_type": "direct"},
+            "2024-01-02": {"nb_visits": 5, "referer_type": "search"},
+            "2024-01-03": [],
+        })]);
+        assert!(schema.properties.is_none());
+        let values = schema.additional_properties.unwrap();
+        let props = values
+            .any_of
+            .as_ref()
+            .and_then(|variants| variants.iter().find(|v| v.properties.is_some()))
+            .and_then(|v| v.properties.as_ref())
+            .unwrap();
+        assert_eq!(props["nb_visits"].schema_type, "integer");
+
+        let weeks = infer_schema(&json!({"2024-01-01,2024-01-07": {"nb_visits": 3}}));
+        assert_eq!(
+            weeks.additional_properties.unwrap().properties.unwrap()["nb_visits"].schema_type,
+            "integer"
+        );
+    }
+
+    #[test]
+    fn test_regular_object_keeps_named_properties() {
+        let schema = infer_schema(&json!({"2024-01-01": 1, "label": "Direct"}));
+        assert!(schema.additional_properties.is_none());
+        assert_eq!(schema.properties.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_nested_array_items_are_merged() {
+        let schema = infer_schema(&json!([[1, 2], ["a"]]));
+        let inner = schema.items.unwrap().items.unwrap();
+        let types: Vec<String> = inner
+            .any_of
+            .unwrap()
+            .into_iter()
+            .map(|s| s.schema_type)
+            .collect();
+        assert_eq!(types, vec!["integer", "string"]);
+    }
+
+    #[test]
+    fn test_arrays_of_objects_with_differing_keys_are_merged() {
+        let schema = analyze_responses(&[
+            json!({"rows": [{"label": "a", "nb_visits": 1}]}),
+            json!({"rows": [{"label": "b", "nb_hits": 2}]}),
+        ]);
+        let rows = &schema.properties.unwrap()["rows"];
+        let row = rows.items.as_ref().unwrap();
+        let mut keys: Vec<&String> = row.properties.as_ref().unwrap().keys().collect();
+        keys.sort();
+        assert_eq!(keys, vec!["label", "nb_hits", "nb_visits"]);
+        assert_eq!(row.required, Some(vec!["label".to_string()]));
+    }
+}
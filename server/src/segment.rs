@@ -0,0 +1,73 @@
+//! Parsing of Matomo segment expressions
+//!
+//! A segment is a list of conditions joined by `;` (AND) and `,` (OR), each
+//! condition being `dimension` `operator` `value`, e.g.
+//! `deviceType==desktop;visitCount>2`.
+
+/// Comparison operators, longest first so `==` wins over `=`-prefixed ones
+const OPERATORS: &[&str] = &["==", "!=", "<=", ">=", "=@", "!@", "=^", "=$", "<", ">"];
+
+/// Extract the dimension names used by a segment, in order of appearance
+///
+/// Returns an error describing the first malformed condition.
+pub fn parse_segment_dimensions(segment: &str) -> Result<Vec<String>, String> {
+    let mut dimensions: Vec<String> = Vec::new();
+
+    for condition in segment.split([';', ',']) {
+        let condition = condition.trim();
+        if condition.is_empty() {
+            return Err(format!("Empty condition in segment '{}'", segment));
+        }
+
+        let Some(position) = condition.find(['=', '!', '<', '>']) else {
+            return Err(format!(
+                "Condition '{}' has no operator (expected one of {})",
+                condition,
+                OPERATORS.join(" ")
+            ));
+        };
+
+        let (dimension, rest) = condition.split_at(position);
+        if !OPERATORS.iter().any(|op| rest.starts_with(op)) {
+            return Err(format!(
+                "Condition '{}' has an invalid operator (expected one of {})",
+                condition,
+                OPERATORS.join(" ")
+            ));
+        }
+        if dimension.is_empty() {
+            return Err(format!("Condition '{}' has no dimension", condition));
+        }
+
+        if !dimensions.iter().any(|d| d == dimension) {
+            dimensions.push(dimension.to_string());
+        }
+    }
+
+    Ok(dimensions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_segment_dimensions() {
+        assert_eq!(
+            parse_segment_dimensions("deviceType==desktop;visitCount>2,visitCount<=10").unwrap(),
+            vec!["deviceType", "visitCount"]
+        );
+        assert_eq!(
+            parse_segment_dimensions("pageUrl=@%2Fblog").unwrap(),
+            vec!["pageUrl"]
+        );
+    }
+
+    #[test]
+    fn test_parse_segment_rejects_malformed_conditions() {
+        assert!(parse_segment_dimensions("deviceType").is_err());
+        assert!(parse_segment_dimensions("==desktop").is_err());
+        assert!(parse_segment_dimensions("deviceType=desktop").is_err());
+        assert!(parse_segment_dimensions("deviceType==desktop;").is_err());
+    }
+}
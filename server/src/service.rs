@@ -1,14 +1,46 @@
-use crate::matomo_client::MatomoClient;
+use crate::cache::ResponseCache;
+use crate::description::compact_description;
+use crate::error::{site_access_error, MatomoError, MatomoErrorKind};
+use crate::http_client::{AuthMode, HttpConfig};
+use crate::matomo_client::{MatomoClient, RetryConfig, MAX_PAGES};
 use crate::openapi::{MatomoTool, OpenApiSpec};
-use reqwest::header::HeaderMap;
+use crate::redact::{PiiRedactor, PiiScope};
+use crate::truncate::truncate_response;
+use crate::validation::{validate_arguments, SITE_LIST_PATTERN};
 use rmcp::handler::server::ServerHandler;
 use rmcp::model::*;
 use rmcp::service::{RequestContext, RoleServer};
 use rmcp::ErrorData;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
-use tracing::{debug, info};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+mod builtin;
+mod in_flight;
+mod metrics;
+mod prompts;
+mod selftest;
+
+pub use in_flight::InFlight;
+pub use metrics::Metrics;
+
+/// Synthetic argument asking `call_tool` to fetch every page of a report
+pub const PAGINATE_ARG: &str = "_paginate";
+
+/// Synthetic argument routing a tool call to another instance (`--config`)
+pub const INSTANCE_ARG: &str = "_instance";
+
+/// Metrics entry of calls to tools that do not exist
+const UNKNOWN_TOOL_KEY: &str = "(unknown)";
+
+/// Parameter selecting the website of a report
+const SITE_ID_PARAM: &str = "idSite";
+
+/// Age after which the accessible sites are fetched again when a call names
+/// a site missing from them
+const SITE_ACCESS_REFRESH: Duration = Duration::from_secs(60);
 
 /// MCP Service for Matomo Analytics
 #[derive(Clone)]
@@ -20,20 +52,79 @@ pub struct MatomoService {
     /// Server info
     matomo_version: String,
     matomo_url: String,
+    /// PII masking applied to tool responses
+    pii_redactor: Arc<PiiRedactor>,
+    pii_scope: PiiScope,
+    /// Masking applied to every tool result (`--redact-fields`)
+    response_redactor: Option<Arc<PiiRedactor>>,
+    /// Serialized size budget of a list_tools page (all tools at once when unset)
+    tools_page_bytes: Option<usize>,
+    /// Maximum number of tools per list_tools page
+    tools_page_size: Option<usize>,
+    /// Responses rendering to more bytes than this are truncated
+    max_response_bytes: Option<usize>,
+    /// idSite injected when a tool call omits it
+    default_site_id: Option<String>,
+    /// period / date injected when a tool call omits them
+    default_period: Option<String>,
+    default_date: Option<String>,
+    /// Language of translated labels, when a tool call does not pick one
+    default_language: Option<String>,
+    /// Send `format_metrics=0` unless a tool call picks a formatting
+    raw_metrics: bool,
+    /// Name of the instance served by `client`, when several are configured
+    default_instance: Option<String>,
+    /// Other instances tool calls can be routed to with `_instance`
+    instances: Arc<BTreeMap<String, Instance>>,
+    /// Call counters of every tool, shared with routed clones
+    metrics: Arc<Metrics>,
+    /// Serving the built-in spec without a Matomo to call (`--offline`)
+    offline: bool,
+    /// Add the HTTP status and duration of the Matomo call to result `_meta`
+    include_call_meta: bool,
+    /// Let `matomo_call` run methods besides read ones (`--allow-write-calls`)
+    allow_write_calls: bool,
+    /// Running tool calls, drained on shutdown
+    in_flight: Arc<InFlight>,
+}
+
+/// A Matomo backend besides the default one
+#[derive(Clone)]
+struct Instance {
+    client: Arc<MatomoClient>,
+    url: String,
+    default_site_id: Option<String>,
 }
 
 impl MatomoService {
     /// Create a new MatomoService from OpenAPI spec
+    ///
+    /// `server` picks one of the spec servers by description; the first
+    /// server is used otherwise.
     pub fn new(
-        spec: OpenApiSpec,
+        mut spec: OpenApiSpec,
+        server: Option<&str>,
         token: Option<String>,
-        extra_headers: &HeaderMap,
+        auth_mode: AuthMode,
+        http: &HttpConfig,
     ) -> anyhow::Result<Self> {
-        let base_url = spec
-            .get_base_url()
-            .ok_or_else(|| anyhow::anyhow!("No server URL in OpenAPI spec"))?;
+        let base_url = match server {
+            Some(name) => {
+                let url = spec.get_base_url_by_description(name)?;
+                info!("Using server '{}': {}", name, url);
+                url
+            }
+            None => spec
+                .get_base_url()
+                .ok_or_else(|| anyhow::anyhow!("No server URL in OpenAPI spec"))?,
+        };
+
+        let client = MatomoClient::new(&base_url, token, auth_mode, http)?;
 
-        let client = MatomoClient::new(&base_url, token, extra_headers)?;
+        // A hand-edited spec may hold operations that cannot become tools
+        for warning in spec.remove_unusable_operations() {
+            warn!("OpenAPI spec: {}", warning);
+        }
         let tools = spec.extract_tools();
 
         info!("Loaded {} tools from OpenAPI spec", tools.len());
@@ -43,21 +134,386 @@ impl MatomoService {
             tools: Arc::new(tools),
             matomo_version: spec.info.version.clone(),
             matomo_url: base_url,
+            pii_redactor: Arc::new(PiiRedactor::default()),
+            pii_scope: PiiScope::LiveOnly,
+            response_redactor: None,
+            tools_page_bytes: None,
+            tools_page_size: None,
+            max_response_bytes: None,
+            default_site_id: None,
+            default_period: None,
+            default_date: None,
+            default_language: None,
+            raw_metrics: false,
+            default_instance: None,
+            instances: Arc::new(BTreeMap::new()),
+            metrics: Arc::new(Metrics::default()),
+            offline: false,
+            include_call_meta: false,
+            allow_write_calls: false,
+            in_flight: Arc::new(InFlight::default()),
         })
     }
 
+    /// Strip HTML from tool descriptions and truncate them to `max_chars`
+    pub fn with_max_description_chars(mut self, max_chars: usize) -> Self {
+        let tools = self
+            .tools
+            .iter()
+            .cloned()
+            .map(|mut tool| {
+                tool.description = compact_description(&tool.description, max_chars);
+                tool
+            })
+            .collect();
+        self.tools = Arc::new(tools);
+        self
+    }
+
+    /// Configure which responses get PII masking and which keys are masked
+    pub fn with_pii_masking(mut self, scope: PiiScope, redactor: PiiRedactor) -> Self {
+        self.pii_scope = scope;
+        self.pii_redactor = Arc::new(redactor);
+        self
+    }
+
+    /// Page list_tools so that each page serializes to at most `max_bytes`
+    pub fn with_tools_page_bytes(mut self, max_bytes: usize) -> Self {
+        self.tools_page_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Drop the tools Matomo marks as deprecated unless `include` is set
+    pub fn with_deprecated_tools(mut self, include: bool) -> Self {
+        if !include {
+            let (deprecated, tools): (Vec<MatomoTool>, Vec<MatomoTool>) =
+                self.tools.iter().cloned().partition(|tool| tool.deprecated);
+            if !deprecated.is_empty() {
+                info!("Hiding {} deprecated tools", deprecated.len());
+            }
+            self.tools = Arc::new(tools);
+        }
+        self
+    }
+
+    /// Page list_tools so that each page holds at most `size` tools
+    pub fn with_tools_page_size(mut self, size: usize) -> Self {
+        self.tools_page_size = Some(size);
+        self
+    }
+
+    /// Truncate tool responses rendering to more than `max_bytes`
+    pub fn with_max_response_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_response_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Use `site_id` for tool calls that do not pass an idSite
+    pub fn with_default_site_id(mut self, site_id: String) -> Self {
+        self.default_site_id = Some(site_id);
+        self
+    }
+
+    /// Use `period` for tool calls that do not pass one
+    pub fn with_default_period(mut self, period: String) -> Self {
+        self.default_period = Some(period);
+        self
+    }
+
+    /// Use `date` for tool calls that do not pass one
+    pub fn with_default_date(mut self, date: String) -> Self {
+        self.default_date = Some(date);
+        self
+    }
+
+    /// Translate labels to `language` (e.g. "fr") unless a tool call picks one
+    pub fn with_default_language(mut self, language: String) -> Self {
+        self.default_language = Some(language);
+        self
+    }
+
+    /// Return raw metric values (0.5 rather than "50%") unless a tool call
+    /// passes `format_metrics`
+    pub fn with_raw_metrics(mut self) -> Self {
+        self.raw_metrics = true;
+        self
+    }
+
+    /// Authenticate with session cookies (sent together with any token)
+    pub fn with_cookies(mut self, cookies: &str) -> anyhow::Result<Self> {
+        self.client = Arc::new((*self.client).clone().with_cookies(cookies)?);
+        Ok(self)
+    }
+
+    /// Retry calls failing while Matomo archives the report
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.client = Arc::new((*self.client).clone().with_retry(retry));
+        self
+    }
+
+    /// Return scalar results (e.g. `VisitsSummary_getVisits`) as `{"value": ...}`
+    /// so every tool yields an object or an array; on by default
+    pub fn with_wrap_scalars(mut self, wrap_scalars: bool) -> Self {
+        self.client = Arc::new((*self.client).clone().with_wrap_scalars(wrap_scalars));
+        self
+    }
+
+    /// Mask matching keys and email / IP values in the result of every tool
+    pub fn with_response_redaction(mut self, redactor: PiiRedactor) -> Self {
+        self.response_redactor = Some(Arc::new(redactor));
+        self
+    }
+
+    /// Reject every call that would reach Matomo, for tool listing demos
+    pub fn with_offline(mut self) -> Self {
+        self.offline = true;
+        self
+    }
+
+    /// Report the HTTP status and duration of each spec tool call in `_meta`
+    pub fn with_call_meta(mut self) -> Self {
+        self.include_call_meta = true;
+        self
+    }
+
+    /// Let `matomo_call` run any method, not only `get*`, `is*`, `has*`... ones
+    pub fn with_write_calls(mut self) -> Self {
+        self.allow_write_calls = true;
+        self
+    }
+
+    /// Cache identical tool calls in memory (set before `client()` is shared)
+    pub fn with_response_cache(mut self, cache: ResponseCache) -> Self {
+        self.client = Arc::new((*self.client).clone().with_cache(cache));
+        self
+    }
+
+    /// Name the default instance, so calls can select it explicitly too
+    pub fn with_default_instance(mut self, name: String) -> Self {
+        self.default_instance = Some(name);
+        self
+    }
+
+    /// Let tool calls passing `_instance: <name>` query another Matomo
+    pub fn with_instance(
+        mut self,
+        name: String,
+        client: MatomoClient,
+        default_site_id: Option<String>,
+    ) -> Self {
+        let url = client.base_url().to_string();
+        Arc::make_mut(&mut self.instances).insert(
+            name,
+            Instance {
+                client: Arc::new(client),
+                url,
+                default_site_id,
+            },
+        );
+        self
+    }
+
+    /// Names of all the configured instances, the default one first
+    fn instance_names(&self) -> Vec<&str> {
+        self.default_instance
+            .as_deref()
+            .into_iter()
+            .chain(self.instances.keys().map(String::as_str))
+            .collect()
+    }
+
+    /// Service bound to the instance picked by `_instance`, if not the default
+    fn routed(
+        &self,
+        params: &mut HashMap<String, serde_json::Value>,
+    ) -> Result<Option<Self>, ErrorData> {
+        let Some(value) = params.remove(INSTANCE_ARG) else {
+            return Ok(None);
+        };
+        let name = value.as_str().ok_or_else(|| {
+            ErrorData::invalid_params(format!("{} must be a string", INSTANCE_ARG), None)
+        })?;
+        if self.default_instance.as_deref() == Some(name) {
+            return Ok(None);
+        }
+        let instance = self.instances.get(name).ok_or_else(|| {
+            ErrorData::invalid_params(
+                format!(
+                    "Unknown instance '{}'. Available instances: {}",
+                    name,
+                    self.instance_names().join(", ")
+                ),
+                None,
+            )
+        })?;
+
+        let mut service = self.clone();
+        service.client = Arc::clone(&instance.client);
+        service.matomo_url = instance.url.clone();
+        service.default_site_id = instance.default_site_id.clone();
+        Ok(Some(service))
+    }
+
+    /// Tool call counters, e.g. to log them on shutdown
+    pub fn metrics(&self) -> Arc<Metrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Running tool calls, to wait for them on shutdown
+    pub fn in_flight(&self) -> Arc<InFlight> {
+        Arc::clone(&self.in_flight)
+    }
+
+    /// Canonical name a call is counted under; unknown names share one entry
+    fn metrics_key(&self, name: &str) -> String {
+        if builtin::BUILTIN_TOOLS.contains(&name) {
+            return name.to_string();
+        }
+        match self.resolve_tool(name) {
+            Ok(tool) => tool.name.clone(),
+            Err(_) => UNKNOWN_TOOL_KEY.to_string(),
+        }
+    }
+
+    /// Shared Matomo client, e.g. to rotate its token
+    pub fn client(&self) -> Arc<MatomoClient> {
+        Arc::clone(&self.client)
+    }
+
     /// Find a tool by name
     fn find_tool(&self, name: &str) -> Option<&MatomoTool> {
         self.tools.iter().find(|t| t.name == name)
     }
 
+    /// Find a tool by name, tolerating naming drift such as `VisitsSummary.get`
+    /// or `visits_summary_get` when there is no exact match
+    fn resolve_tool(&self, name: &str) -> Result<&MatomoTool, String> {
+        if let Some(tool) = self.find_tool(name) {
+            return Ok(tool);
+        }
+
+        let key = normalize_tool_name(name);
+        let candidates: Vec<&MatomoTool> = self
+            .tools
+            .iter()
+            .filter(|tool| normalize_tool_name(&tool.name) == key)
+            .collect();
+        match candidates.as_slice() {
+            [tool] => {
+                debug!("Resolved tool name {} to {}", name, tool.name);
+                Ok(tool)
+            }
+            [] => Err(format!("Unknown tool: {}", name)),
+            _ => Err(format!(
+                "Ambiguous tool name {}, candidates: {}",
+                name,
+                candidates
+                    .iter()
+                    .map(|tool| tool.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+
+    /// Server-side defaults of the parameters tool calls may omit
+    fn parameter_defaults(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        [
+            (SITE_ID_PARAM, self.default_site_id.as_deref()),
+            ("period", self.default_period.as_deref()),
+            ("date", self.default_date.as_deref()),
+            ("language", self.default_language.as_deref()),
+            ("format_metrics", self.raw_metrics.then_some("0")),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+    }
+
+    /// Reject a call for an `idSite` the token cannot view, naming the sites it can
+    ///
+    /// Matomo's own error for this case does not say which sites would work.
+    /// The check is skipped for `all`, or when the access list is unavailable.
+    async fn check_site_access(
+        &self,
+        params: &HashMap<String, serde_json::Value>,
+    ) -> Result<(), ErrorData> {
+        let Some(requested) = params.get(SITE_ID_PARAM).and_then(site_ids) else {
+            return Ok(());
+        };
+
+        let mut max_age = None;
+        loop {
+            let accessible = match self.client.accessible_site_ids(max_age).await {
+                Ok(accessible) => accessible,
+                Err(e) => {
+                    debug!("Skipping site access check: {:#}", e);
+                    return Ok(());
+                }
+            };
+            match requested.iter().find(|id| !accessible.contains(id)) {
+                None => return Ok(()),
+                // The site may have been created since the list was fetched
+                Some(_) if max_age.is_none() => max_age = Some(SITE_ACCESS_REFRESH),
+                Some(&id) => return Err(site_access_error(id, &accessible)),
+            }
+        }
+    }
+
+    /// Replace a non-JSON `format` argument by JSON when the response of
+    /// `module` gets masked, returning a note for the caller
+    ///
+    /// PII masking and `--redact-fields` rewrite JSON values: a raw
+    /// CSV/TSV/XML body would reach the client with the data in clear.
+    fn force_json_if_masked(
+        &self,
+        module: &str,
+        params: &mut HashMap<String, serde_json::Value>,
+    ) -> Option<String> {
+        if !self.pii_scope.applies_to(module) && self.response_redactor.is_none() {
+            return None;
+        }
+        let format = non_json_format(params)?;
+        let note = format!(
+            "format={} was replaced by JSON, the only format personal data can be masked in",
+            format
+        );
+        params.insert("format".to_string(), "JSON".into());
+        Some(note)
+    }
+
+    /// Fill in defaults and validate the arguments of a spec tool call
+    fn prepare_arguments(
+        &self,
+        tool: &MatomoTool,
+        params: &mut HashMap<String, serde_json::Value>,
+    ) -> Result<Vec<String>, String> {
+        // Inject the defaults unless the caller picked a value
+        for (name, value) in self.parameter_defaults() {
+            let has_param = tool.parameters.iter().any(|p| p.name == name);
+            if has_param && params.get(name).is_none_or(|v| v.is_null()) {
+                params.insert(
+                    name.to_string(),
+                    serde_json::Value::String(value.to_string()),
+                );
+            }
+        }
+
+        validate_arguments(tool, params)
+    }
+
     /// Convert MatomoTool to MCP Tool definition
     fn tool_to_mcp(&self, tool: &MatomoTool) -> Tool {
+        let output_schema = output_schema(tool.response_schema.as_ref());
+
         // Build JSON schema for parameters
         let mut properties = serde_json::Map::new();
         let mut required = Vec::new();
 
         for param in &tool.parameters {
+            // Raw CSV/XML bodies cannot match a declared output schema
+            if param.name == "format" && output_schema.is_some() {
+                continue;
+            }
             let mut prop = serde_json::Map::new();
 
             // Map OpenAPI type to JSON Schema type
@@ -73,18 +529,64 @@ impl MatomoService {
                 "type".to_string(),
                 serde_json::Value::String(json_type.to_string()),
             );
-
-            if let Some(ref desc) = param.description {
+            // Multi-site reports also take `all` or a list of ids such as "1,3"
+            if param.name == SITE_ID_PARAM && json_type == "integer" {
+                prop.insert("type".to_string(), serde_json::json!(["integer", "string"]));
+                prop.insert("pattern".to_string(), SITE_LIST_PATTERN.into());
+            }
+            if json_type == "array" {
+                let item_type = param.item_type.as_deref().unwrap_or("string");
                 prop.insert(
-                    "description".to_string(),
-                    serde_json::Value::String(desc.clone()),
+                    "items".to_string(),
+                    serde_json::json!({ "type": item_type }),
                 );
             }
 
+            let mut description = param.description.clone();
+            let mut param_required = param.required;
+            if let Some((name, value)) = self
+                .parameter_defaults()
+                .find(|(name, _)| *name == param.name)
+            {
+                // The server fills in the value, an explicit one takes precedence
+                let precedence = format!(
+                    "Defaults to {} when omitted; an explicit {} always takes precedence.",
+                    value, name
+                );
+                description = Some(match description {
+                    Some(desc) => format!("{} {}", desc, precedence),
+                    None => precedence,
+                });
+                param_required = false;
+            }
+
+            if param.deprecated {
+                description = Some(match description {
+                    Some(desc) => format!("Deprecated. {}", desc),
+                    None => "Deprecated.".to_string(),
+                });
+                prop.insert("deprecated".to_string(), serde_json::Value::Bool(true));
+            }
+
+            if let Some(desc) = description {
+                prop.insert("description".to_string(), serde_json::Value::String(desc));
+            }
+
             if let Some(ref default) = param.default {
                 prop.insert("default".to_string(), default.clone());
             }
 
+            if let Some(ref example) = param.example {
+                prop.insert("examples".to_string(), serde_json::json!([example]));
+            }
+
+            if let Some(minimum) = param.minimum {
+                prop.insert("minimum".to_string(), minimum.into());
+            }
+            if let Some(maximum) = param.maximum {
+                prop.insert("maximum".to_string(), maximum.into());
+            }
+
             if let Some(ref enum_vals) = param.enum_values {
                 let enum_arr: Vec<serde_json::Value> = enum_vals
                     .iter()
@@ -95,11 +597,26 @@ impl MatomoService {
 
             properties.insert(param.name.clone(), serde_json::Value::Object(prop));
 
-            if param.required {
+            if param_required {
                 required.push(param.name.clone());
             }
         }
 
+        // Reports supporting filter_limit can be fetched page by page
+        if tool.parameters.iter().any(|p| p.name == "filter_limit") {
+            properties.insert(
+                PAGINATE_ARG.to_string(),
+                serde_json::json!({
+                    "type": "boolean",
+                    "description": format!(
+                        "Fetch all rows by following filter_offset page by page \
+                         (filter_limit is used as the page size, at most {} pages)",
+                        MAX_PAGES
+                    ),
+                }),
+            );
+        }
+
         let mut schema = serde_json::Map::new();
         schema.insert(
             "type".to_string(),
@@ -128,17 +645,293 @@ impl MatomoService {
             annotations: None,
             icons: None,
             meta: None,
-            output_schema: None,
+            output_schema,
             title: None,
         }
     }
 }
 
+/// Output schema of a tool, from its response schema
+///
+/// MCP requires output schemas to describe objects, so array and scalar
+/// schemas are wrapped in `{"value": ...}` like `structured_content` wraps
+/// the results. The generic `{"type": "object"}` placeholder tells clients
+/// nothing and yields no output schema.
+///
+/// Calls over several periods (`date=last7`, `previous7` or a list of dates)
+/// return one result per period keyed by date, the second `anyOf` branch.
+fn output_schema(response_schema: Option<&serde_json::Value>) -> Option<Arc<JsonObject>> {
+    let schema = to_json_schema(response_schema?);
+    let obj = schema.as_object()?;
+
+    let is_object = obj.get("type").and_then(|t| t.as_str()) == Some("object")
+        && !obj.contains_key("anyOf")
+        && !obj.contains_key("oneOf");
+    let single = if is_object {
+        let has_properties = obj
+            .get("properties")
+            .and_then(|p| p.as_object())
+            .is_some_and(|p| !p.is_empty());
+        if !has_properties && !obj.contains_key("additionalProperties") {
+            return None;
+        }
+        schema.clone()
+    } else {
+        serde_json::json!({
+            "type": "object",
+            "properties": { "value": schema },
+        })
+    };
+    let per_period = serde_json::json!({
+        "type": "object",
+        "description": "One result per period, keyed by date",
+        "additionalProperties": schema,
+    });
+
+    let output = serde_json::json!({
+        "type": "object",
+        "anyOf": [single, per_period],
+    });
+    output.as_object().cloned().map(Arc::new)
+}
+
+/// Value of a `format` argument asking for another format than JSON
+fn non_json_format(params: &HashMap<String, serde_json::Value>) -> Option<&str> {
+    let format = params.get("format")?.as_str()?;
+    (!format.is_empty() && !format.eq_ignore_ascii_case("json")).then_some(format)
+}
+
+/// Convert an OpenAPI 3.0 schema to JSON Schema
+///
+/// Only `nullable` differs in the subset generated here: it becomes a
+/// `["type", "null"]` type union. The placeholder `object` type OpenAPI 3.0
+/// unions carry is dropped, as it would contradict their variants. Nested
+/// schemas are converted recursively.
+fn to_json_schema(schema: &serde_json::Value) -> serde_json::Value {
+    match schema {
+        serde_json::Value::Object(obj) => {
+            let mut converted: JsonObject = obj
+                .iter()
+                .filter(|(key, _)| key.as_str() != "nullable")
+                .map(|(key, value)| (key.clone(), to_json_schema(value)))
+                .collect();
+            let nullable = obj.get("nullable").and_then(|n| n.as_bool()) == Some(true);
+            if let (true, Some(serde_json::Value::String(t))) = (nullable, obj.get("type")) {
+                if t != "null" {
+                    converted.insert("type".to_string(), serde_json::json!([t, "null"]));
+                }
+            }
+            let is_union = obj.contains_key("anyOf") || obj.contains_key("oneOf");
+            if is_union
+                && !obj.contains_key("properties")
+                && obj.get("type") == Some(&"object".into())
+            {
+                converted.remove("type");
+            }
+            serde_json::Value::Object(converted)
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.iter().map(to_json_schema).collect())
+        }
+        other => other.clone(),
+    }
+}
+
+/// Tool name without case and separators: `VisitsSummary.get`,
+/// `visits_summary_get` and `VisitsSummary_get` all become `visitssummaryget`
+fn normalize_tool_name(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Structured content of a tool result
+///
+/// Objects are returned as-is; arrays and scalars are wrapped in
+/// `{"value": ...}` since structured content must be an object. Strings are
+/// left out as they may be raw CSV/XML bodies.
+fn structured_content(result: serde_json::Value) -> Option<serde_json::Value> {
+    match result {
+        serde_json::Value::Object(_) => Some(result),
+        serde_json::Value::String(_) | serde_json::Value::Null => None,
+        other => Some(serde_json::json!({ "value": other })),
+    }
+}
+
+/// Take tools from `start` while the page holds at most `max_tools` tools
+/// serializing to at most `max_bytes`
+///
+/// At least one tool is always returned, even if it alone exceeds the budget,
+/// so that paging always makes progress. Returns the page and the index of the
+/// next page, if any.
+fn page_tools(
+    tools: Vec<Tool>,
+    start: usize,
+    max_tools: Option<usize>,
+    max_bytes: Option<usize>,
+) -> (Vec<Tool>, Option<usize>) {
+    let total = tools.len();
+    let max_tools = max_tools.unwrap_or(usize::MAX).max(1);
+    let mut page = Vec::new();
+    let mut used = 0;
+
+    for tool in tools.into_iter().skip(start).take(max_tools) {
+        let size = max_bytes.map_or(0, |_| {
+            serde_json::to_vec(&tool).map(|v| v.len()).unwrap_or(0)
+        });
+        if !page.is_empty() && max_bytes.is_some_and(|max_bytes| used + size > max_bytes) {
+            break;
+        }
+        used += size;
+        page.push(tool);
+    }
+
+    let end = start + page.len();
+    (page, (end < total).then_some(end))
+}
+
+impl MatomoService {
+    /// Run a built-in or spec tool
+    async fn run_tool(
+        &self,
+        tool_name: &str,
+        arguments: Option<JsonObject>,
+    ) -> Result<CallToolResult, ErrorData> {
+        // Extract parameters from arguments
+        let mut params: HashMap<String, serde_json::Value> = match arguments {
+            Some(map) => map.into_iter().collect(),
+            None => HashMap::new(),
+        };
+        if self.offline && !builtin::works_offline(tool_name) {
+            return Err(ErrorData::invalid_request(
+                format!(
+                    "'{}' is unavailable in offline mode: the server has no Matomo \
+                     connection (restart it with --url or --openapi to call tools)",
+                    tool_name
+                ),
+                None,
+            ));
+        }
+        let routed = self.routed(&mut params)?;
+        let this = routed.as_ref().unwrap_or(self);
+
+        // Built-in tools take precedence over spec tools
+        if let Some(result) = this.call_builtin(tool_name, &params).await {
+            return result;
+        }
+
+        // Find the tool
+        let tool = this
+            .resolve_tool(tool_name)
+            .map_err(|e| ErrorData::invalid_params(e, None))?;
+        let paginate = params
+            .remove(PAGINATE_ARG)
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let mut corrections = this
+            .prepare_arguments(tool, &mut params)
+            .map_err(|e| ErrorData::invalid_params(e, None))?;
+        corrections.extend(this.force_json_if_masked(&tool.module, &mut params));
+        if let Some(format) = non_json_format(&params) {
+            if output_schema(tool.response_schema.as_ref()).is_some() {
+                return Err(ErrorData::invalid_params(
+                    format!(
+                        "{} returns structured JSON matching its output schema; \
+                         format={} is not available for it",
+                        tool.name, format
+                    ),
+                    None,
+                ));
+            }
+        }
+        this.check_site_access(&params).await?;
+
+        // Call Matomo API
+        let started = Instant::now();
+        let (result, status) = if paginate {
+            let result = this
+                .client
+                .call_method_paginated(&tool.module, &tool.action, params)
+                .await;
+            (result, None)
+        } else {
+            this.client
+                .call_method_with_status(&tool.module, &tool.action, params)
+                .await
+        };
+        let call_meta = this
+            .include_call_meta
+            .then(|| call_meta(status, started.elapsed()));
+
+        match result {
+            Ok(mut result) => {
+                if this.pii_scope.applies_to(&tool.module) {
+                    this.pii_redactor.redact(&mut result);
+                }
+
+                // Keep oversized reports within the client's context budget
+                let truncated = this
+                    .max_response_bytes
+                    .and_then(|max_bytes| truncate_response(&result, max_bytes));
+                let note = truncated.map(|truncated| {
+                    result = truncated.value;
+                    truncated.note
+                });
+
+                // Overview reports return a single row: document its metric units
+                let mut meta = (result.is_object() && !tool.metric_units.is_empty()).then(|| {
+                    let mut meta = Meta::new();
+                    meta.0.insert(
+                        "metric_units".to_string(),
+                        serde_json::json!(tool.metric_units),
+                    );
+                    meta
+                });
+                if let Some(call_meta) = call_meta {
+                    meta.get_or_insert_with(Meta::new).0.extend(call_meta.0);
+                }
+
+                // Format the response nicely (raw CSV/TSV/XML bodies are returned as-is);
+                // the result is moved into the structured content rather than copied
+                let (text, structured) = match result {
+                    serde_json::Value::String(s) => (s, None),
+                    result => (
+                        serde_json::to_string_pretty(&result)
+                            .unwrap_or_else(|_| result.to_string()),
+                        structured_content(result),
+                    ),
+                };
+                let mut content = vec![Content::text(text)];
+                content.extend(note.map(Content::text));
+                if !corrections.is_empty() {
+                    content.push(Content::text(format!("Note: {}", corrections.join("; "))));
+                }
+
+                Ok(CallToolResult {
+                    content,
+                    is_error: Some(false),
+                    meta,
+                    structured_content: structured,
+                })
+            }
+            Err(e) => error_result(e).map(|mut result| {
+                result.meta = call_meta;
+                result
+            }),
+        }
+    }
+}
+
 impl ServerHandler for MatomoService {
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
             protocol_version: ProtocolVersion::V_2024_11_05,
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            capabilities: ServerCapabilities::builder()
+                .enable_tools()
+                .enable_prompts()
+                .build(),
             server_info: Implementation {
                 name: "mcp-matomo".to_string(),
                 version: env!("CARGO_PKG_VERSION").to_string(),
@@ -151,24 +944,94 @@ impl ServerHandler for MatomoService {
                  Connected to: {}\n\
                  Matomo version: {}\n\
                  Available tools: {}\n\n\
-                 Use these tools to query analytics data from your Matomo instance.",
+                 Use these tools to query analytics data from your Matomo instance. \
+                 Call matomo_ping first to check connectivity and authentication. \
+                 Prompts provide ready-made analyses (weekly summary, top referrers, ...).{}",
                 self.matomo_url,
                 self.matomo_version,
-                self.tools.len()
+                self.tools.len(),
+                if self.instances.is_empty() {
+                    String::new()
+                } else {
+                    format!(
+                        "\nInstances: {} (pass {} to query another instance than {})",
+                        self.instance_names().join(", "),
+                        INSTANCE_ARG,
+                        self.default_instance.as_deref().unwrap_or_default()
+                    )
+                }
             )),
         }
     }
 
-    async fn list_tools(
+    async fn list_prompts(
         &self,
         _request: Option<PaginatedRequestParam>,
         _context: RequestContext<RoleServer>,
+    ) -> Result<ListPromptsResult, ErrorData> {
+        Ok(ListPromptsResult {
+            prompts: self.prompts(),
+            next_cursor: None,
+            meta: None,
+        })
+    }
+
+    async fn get_prompt(
+        &self,
+        request: GetPromptRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<GetPromptResult, ErrorData> {
+        self.render_prompt(&request.name, request.arguments.as_ref())
+    }
+
+    async fn list_tools(
+        &self,
+        request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
     ) -> Result<ListToolsResult, ErrorData> {
         debug!("Listing {} tools", self.tools.len());
-        let tools: Vec<Tool> = self.tools.iter().map(|t| self.tool_to_mcp(t)).collect();
+        let mut tools = self.builtin_tools();
+        tools.extend(self.tools.iter().map(|t| self.tool_to_mcp(t)));
+        if !self.instances.is_empty() {
+            let property = serde_json::json!({
+                "type": "string",
+                "enum": self.instance_names(),
+                "description": format!(
+                    "Matomo instance to query (default: {})",
+                    self.default_instance.as_deref().unwrap_or_default()
+                ),
+            });
+            for tool in &mut tools {
+                let schema = Arc::make_mut(&mut tool.input_schema);
+                if let Some(serde_json::Value::Object(properties)) = schema.get_mut("properties") {
+                    properties.insert(INSTANCE_ARG.to_string(), property.clone());
+                }
+            }
+        }
+
+        if self.tools_page_bytes.is_none() && self.tools_page_size.is_none() {
+            return Ok(ListToolsResult {
+                tools,
+                next_cursor: None,
+                meta: None,
+            });
+        };
+
+        let start = match request.and_then(|r| r.cursor) {
+            Some(cursor) => cursor
+                .parse::<usize>()
+                .ok()
+                .filter(|&start| start <= tools.len())
+                .ok_or_else(|| {
+                    ErrorData::invalid_params(format!("Invalid cursor: {}", cursor), None)
+                })?,
+            None => 0,
+        };
+        let (tools, next) = page_tools(tools, start, self.tools_page_size, self.tools_page_bytes);
+
         Ok(ListToolsResult {
             tools,
-            next_cursor: None,
+            next_cursor: next.map(|n| n.to_string()),
             meta: None,
         })
     }
@@ -181,41 +1044,584 @@ impl ServerHandler for MatomoService {
         let tool_name = request.name.as_ref();
         debug!("Calling tool: {}", tool_name);
 
-        // Find the tool
-        let tool = self.find_tool(tool_name).ok_or_else(|| {
-            ErrorData::invalid_params(format!("Unknown tool: {}", tool_name), None)
-        })?;
+        let Some(_call) = self.in_flight.start() else {
+            return Err(ErrorData::invalid_request(
+                "The server is shutting down and no longer accepts tool calls",
+                None,
+            ));
+        };
 
-        // Extract parameters from arguments
-        let params: HashMap<String, serde_json::Value> = match request.arguments {
-            Some(map) => map.into_iter().collect(),
-            None => HashMap::new(),
+        let started = Instant::now();
+        let mut result = self.run_tool(tool_name, request.arguments).await;
+        if let (Some(redactor), Ok(result)) = (&self.response_redactor, &mut result) {
+            redact_result(redactor, result);
+        }
+        let failed = result
+            .as_ref()
+            .map_or(true, |result| result.is_error == Some(true));
+        self.metrics
+            .record(&self.metrics_key(tool_name), failed, started.elapsed());
+        result
+    }
+}
+
+/// `_meta` entries of `--include-call-meta`
+///
+/// `http_status` is null when the response came from the cache or, for
+/// paginated calls, spans several requests. Calls failing with a recognized
+/// Matomo error become MCP errors, whose data carries the status instead.
+fn call_meta(status: Option<reqwest::StatusCode>, duration: Duration) -> Meta {
+    let mut meta = Meta::new();
+    meta.0.insert(
+        "http_status".to_string(),
+        serde_json::json!(status.map(|status| status.as_u16())),
+    );
+    meta.0.insert(
+        "duration_ms".to_string(),
+        serde_json::json!(duration.as_millis() as u64),
+    );
+    meta
+}
+
+/// Mask the structured content and the JSON text blocks of a tool result
+///
+/// Text blocks that are not JSON (notes) are left as they are; responses
+/// are fetched as JSON while redaction is on, see `force_json_if_masked`.
+fn redact_result(redactor: &PiiRedactor, result: &mut CallToolResult) {
+    if let Some(ref mut structured) = result.structured_content {
+        redactor.redact(structured);
+    }
+    for content in &mut result.content {
+        let RawContent::Text(ref mut block) = content.raw else {
+            continue;
         };
+        let Ok(mut value) = serde_json::from_str::<serde_json::Value>(&block.text) else {
+            continue;
+        };
+        if value.is_object() || value.is_array() {
+            redactor.redact(&mut value);
+            if let Ok(text) = serde_json::to_string_pretty(&value) {
+                block.text = text;
+            }
+        }
+    }
+}
 
-        // Call Matomo API
-        match self
-            .client
-            .call_method(&tool.module, &tool.action, params)
-            .await
-        {
-            Ok(result) => {
-                // Format the response nicely
-                let text =
-                    serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string());
+/// Site ids of a validated `idSite` argument (`3` or `"1,3"`), `None` for `all`
+fn site_ids(value: &serde_json::Value) -> Option<Vec<u64>> {
+    match value {
+        serde_json::Value::Number(n) => n.as_u64().map(|id| vec![id]),
+        serde_json::Value::String(s) => s.split(',').map(|id| id.trim().parse().ok()).collect(),
+        _ => None,
+    }
+}
 
-                Ok(CallToolResult {
-                    content: vec![Content::text(text)],
-                    is_error: Some(false),
-                    meta: None,
-                    structured_content: None,
-                })
+/// Report a failed Matomo call
+///
+/// Recognized Matomo errors (auth required, no access, invalid site) become
+/// MCP errors with a stable `code` in their data; anything else is returned
+/// as a tool result flagged `is_error` so the model can read the message.
+fn error_result(e: anyhow::Error) -> Result<CallToolResult, ErrorData> {
+    if let Some(error) = e.downcast_ref::<MatomoError>() {
+        if error.kind != MatomoErrorKind::Other {
+            return Err(error.to_error_data());
+        }
+    }
+    Ok(CallToolResult {
+        content: vec![Content::text(format!("Error: {}", e))],
+        is_error: Some(true),
+        meta: None,
+        structured_content: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(name: &str, description: &str) -> Tool {
+        Tool {
+            name: Cow::Owned(name.to_string()),
+            description: Some(Cow::Owned(description.to_string())),
+            input_schema: Arc::new(serde_json::Map::new()),
+            annotations: None,
+            icons: None,
+            meta: None,
+            output_schema: None,
+            title: None,
+        }
+    }
+
+    fn size(tool: &Tool) -> usize {
+        serde_json::to_vec(tool).unwrap().len()
+    }
+
+    #[test]
+    fn test_call_meta() {
+        let meta = call_meta(Some(reqwest::StatusCode::OK), Duration::from_millis(1500));
+        assert_eq!(meta.0["http_status"], 200);
+        assert_eq!(meta.0["duration_ms"], 1500);
+        assert!(call_meta(None, Duration::ZERO).0["http_status"].is_null());
+    }
+
+    #[test]
+    fn test_redact_result_masks_text_and_structured_content() {
+        let value = serde_json::json!({"visitIp": "1.2.3.4", "nb_visits": 3});
+        let mut result = CallToolResult {
+            content: vec![
+                Content::text(serde_json::to_string_pretty(&value).unwrap()),
+                Content::text("Note: contact jane@example.com"),
+            ],
+            is_error: Some(false),
+            meta: None,
+            structured_content: Some(value),
+        };
+        redact_result(
+            &PiiRedactor::new(Vec::new()).with_value_detection(),
+            &mut result,
+        );
+
+        let structured = result.structured_content.unwrap();
+        assert_eq!(structured["visitIp"], crate::redact::REDACTED);
+        assert_eq!(structured["nb_visits"], 3);
+        let text = result.content[0].as_text().unwrap().text.clone();
+        assert!(!text.contains("1.2.3.4"), "{}", text);
+        assert_eq!(
+            result.content[1].as_text().unwrap().text,
+            "Note: contact jane@example.com"
+        );
+    }
+
+    #[test]
+    fn test_site_ids_of_argument() {
+        assert_eq!(site_ids(&serde_json::json!(3)), Some(vec![3]));
+        assert_eq!(site_ids(&serde_json::json!("1, 3")), Some(vec![1, 3]));
+        assert_eq!(site_ids(&serde_json::json!("all")), None);
+    }
+
+    #[test]
+    fn test_page_tools_packs_tools_under_budget() {
+        let tools: Vec<Tool> = (0..5).map(|i| tool(&format!("t{}", i), "x")).collect();
+        let budget = size(&tools[0]) * 2;
+
+        let (page, next) = page_tools(tools.clone(), 0, None, Some(budget));
+        assert_eq!(page.len(), 2);
+        assert_eq!(next, Some(2));
+
+        let (page, next) = page_tools(tools, 4, None, Some(budget));
+        assert_eq!(page.len(), 1);
+        assert_eq!(next, None);
+    }
+
+    #[test]
+    fn test_page_tools_returns_oversized_tool() {
+        let tools = vec![tool("big", &"x".repeat(1000)), tool("small", "")];
+        let (page, next) = page_tools(tools, 0, None, Some(10));
+        assert_eq!(page.len(), 1);
+        assert_eq!(next, Some(1));
+    }
+
+    #[test]
+    fn test_page_tools_by_count() {
+        let tools: Vec<Tool> = (0..5).map(|i| tool(&format!("t{}", i), "x")).collect();
+
+        let (page, next) = page_tools(tools.clone(), 2, Some(2), None);
+        assert_eq!(page[0].name, "t2");
+        assert_eq!(page.len(), 2);
+        assert_eq!(next, Some(4));
+
+        // The tighter of both limits wins
+        let budget = size(&tools[0]) * 3;
+        let (page, next) = page_tools(tools, 0, Some(4), Some(budget));
+        assert_eq!(page.len(), 3);
+        assert_eq!(next, Some(3));
+    }
+
+    #[test]
+    fn test_normalize_tool_name() {
+        for name in [
+            "VisitsSummary.get",
+            "visits_summary_get",
+            "VISITSSUMMARY_GET",
+        ] {
+            assert_eq!(
+                normalize_tool_name(name),
+                normalize_tool_name("VisitsSummary_get")
+            );
+        }
+        assert_ne!(
+            normalize_tool_name("Actions.getPageUrls"),
+            normalize_tool_name("Actions_getPageUrl")
+        );
+    }
+
+    #[test]
+    fn test_structured_content_wraps_non_objects() {
+        let object = serde_json::json!({"nb_visits": 3});
+        assert_eq!(structured_content(object.clone()), Some(object));
+        assert_eq!(
+            structured_content(serde_json::json!([1, 2])),
+            Some(serde_json::json!({"value": [1, 2]}))
+        );
+        assert_eq!(structured_content(serde_json::json!("a,b\n1,2")), None);
+    }
+
+    #[test]
+    fn test_output_schema_skips_placeholder() {
+        let placeholder = serde_json::json!({"type": "object", "description": "API response"});
+        assert!(output_schema(Some(&placeholder)).is_none());
+
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"nb_visits": {"type": "integer"}}
+        });
+        assert!(output_schema(Some(&schema)).is_some());
+        assert!(output_schema(None).is_none());
+    }
+
+    #[test]
+    fn test_output_schema_wraps_arrays_and_converts_nullable() {
+        let schema = serde_json::json!({
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "label": {"type": "string", "nullable": true},
+                    "subtable": {"type": "array", "items": {"type": "object"}}
+                }
             }
-            Err(e) => Ok(CallToolResult {
-                content: vec![Content::text(format!("Error: {}", e))],
-                is_error: Some(true),
-                meta: None,
-                structured_content: None,
-            }),
+        });
+        let output = output_schema(Some(&schema)).unwrap();
+        assert_eq!(output["type"], "object");
+        let label = &output["anyOf"][0]["properties"]["value"]["items"]["properties"]["label"];
+        assert_eq!(label["type"], serde_json::json!(["string", "null"]));
+        assert!(label.get("nullable").is_none());
+    }
+
+    #[test]
+    fn test_union_placeholder_type_is_dropped() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"bounce_rate": {
+                "type": "object",
+                "anyOf": [{"type": "number"}, {"type": "string"}]
+            }}
+        });
+        let converted = to_json_schema(&schema);
+        assert_eq!(converted["type"], "object");
+        assert!(converted["properties"]["bounce_rate"].get("type").is_none());
+    }
+
+    #[test]
+    fn test_output_schema_describes_results_keyed_by_date() {
+        let row = serde_json::json!({
+            "type": "object",
+            "properties": {"nb_visits": {"type": "integer"}}
+        });
+        let output = output_schema(Some(&row)).unwrap();
+        assert_eq!(output["anyOf"][0], row);
+        assert_eq!(output["anyOf"][1]["additionalProperties"], row);
+
+        let rows = serde_json::json!({"type": "array", "items": row});
+        let output = output_schema(Some(&rows)).unwrap();
+        assert_eq!(output["anyOf"][1]["additionalProperties"], rows);
+    }
+
+    /// Answer one HTTP request per `(status, body)`, in order, yielding the
+    /// requests' form bodies
+    async fn serve(
+        responses: &[(u16, &'static str)],
+    ) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let responses = responses.to_vec();
+        let handle = tokio::spawn(async move {
+            let mut forms = Vec::new();
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                let form = loop {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    let Some((head, form)) = text.split_once("\r\n\r\n") else {
+                        continue;
+                    };
+                    let length = head
+                        .lines()
+                        .find_map(|line| {
+                            line.to_ascii_lowercase()
+                                .strip_prefix("content-length:")?
+                                .trim()
+                                .parse()
+                                .ok()
+                        })
+                        .unwrap_or(0);
+                    if form.len() >= length {
+                        break form.to_string();
+                    }
+                };
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+                forms.push(form);
+            }
+            forms
+        });
+        (url, handle)
+    }
+
+    /// Service with a single `Module.action` tool, calling the Matomo at `url`
+    fn service_with_tool(url: &str, module: &str, action: &str) -> MatomoService {
+        let spec: OpenApiSpec = serde_json::from_value(serde_json::json!({
+            "openapi": "3.0.3",
+            "info": {"title": "Matomo API", "version": "5.1.0"},
+            "servers": [{"url": url}],
+            "paths": {
+                format!("/index.php/{}/{}", module, action): {"get": {
+                    "operationId": format!("{}_{}", module, action),
+                    "x-matomo-module": module,
+                    "x-matomo-action": action,
+                    "parameters": [{
+                        "name": "idSite",
+                        "in": "query",
+                        "required": false,
+                        "schema": {"type": "integer"}
+                    }, {
+                        "name": "format",
+                        "in": "query",
+                        "required": false,
+                        "schema": {"type": "string", "enum": ["JSON", "XML", "CSV", "TSV"]}
+                    }],
+                    "responses": {"200": {"description": "Successful response"}}
+                }}
+            }
+        }))
+        .unwrap();
+        MatomoService::new(
+            spec,
+            None,
+            Some("secret".to_string()),
+            AuthMode::TokenAuthForm,
+            &HttpConfig::default(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_masked_module_is_fetched_as_json() {
+        let (url, request) = serve(&[(200, r#"[{"idVisit": 1, "visitIp": "203.0.113.7"}]"#)]).await;
+        let service = service_with_tool(&url, "Live", "getLastVisitsDetails");
+
+        let mut arguments = JsonObject::new();
+        arguments.insert("format".to_string(), serde_json::json!("CSV"));
+        let result = service
+            .run_tool("Live_getLastVisitsDetails", Some(arguments))
+            .await
+            .unwrap();
+
+        assert!(request.await.unwrap()[0].contains("format=JSON"));
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(!text.contains("203.0.113.7"), "{}", text);
+        assert!(text.contains(crate::redact::REDACTED), "{}", text);
+        assert!(result.content[1]
+            .as_text()
+            .unwrap()
+            .text
+            .contains("format=CSV was replaced by JSON"));
+    }
+
+    #[tokio::test]
+    async fn test_routed_calls_use_the_instance_defaults() {
+        let (url, requests) = serve(&[(200, "[7]"), (200, r#"{"nb_visits": 3}"#)]).await;
+        let other = service_with_tool(&url, "VisitsSummary", "get").client();
+        let service = service_with_tool("http://127.0.0.1:9", "VisitsSummary", "get")
+            .with_default_site_id("1".to_string())
+            .with_default_instance("main".to_string())
+            .with_instance("other".to_string(), (*other).clone(), Some("7".to_string()));
+
+        let mut arguments = JsonObject::new();
+        arguments.insert(INSTANCE_ARG.to_string(), serde_json::json!("other"));
+        let result = service
+            .run_tool("VisitsSummary_get", Some(arguments))
+            .await
+            .unwrap();
+
+        assert_eq!(result.is_error, Some(false));
+        assert!(requests.await.unwrap()[1].contains("idSite=7"));
+    }
+
+    #[tokio::test]
+    async fn test_site_access_is_checked_for_site_lists() {
+        let (url, _requests) = serve(&[(200, "[1]")]).await;
+        let service = service_with_tool(&url, "VisitsSummary", "get");
+
+        let mut arguments = JsonObject::new();
+        arguments.insert(SITE_ID_PARAM.to_string(), serde_json::json!("1, 3"));
+        let err = service
+            .run_tool("VisitsSummary_get", Some(arguments))
+            .await
+            .unwrap_err();
+        assert_eq!(err.data.unwrap()["idSite"], 3);
+    }
+
+    #[test]
+    fn test_builtin_tool_names_are_listed() {
+        let service = service_with_tool("http://127.0.0.1:9", "VisitsSummary", "get");
+        let names: Vec<String> = service
+            .builtin_tools()
+            .into_iter()
+            .map(|tool| tool.name.to_string())
+            .collect();
+        assert_eq!(names, builtin::BUILTIN_TOOLS);
+    }
+
+    #[tokio::test]
+    async fn test_raw_calls_check_site_access() {
+        let (url, _requests) = serve(&[(200, "[1]")]).await;
+        let service = service_with_tool(&url, "VisitsSummary", "get");
+
+        let mut arguments = JsonObject::new();
+        arguments.insert("method".to_string(), serde_json::json!("VisitsSummary.get"));
+        arguments.insert("params".to_string(), serde_json::json!({"idSite": 3}));
+        let err = service
+            .run_tool("matomo_call", Some(arguments))
+            .await
+            .unwrap_err();
+        assert_eq!(err.data.unwrap()["idSite"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_multi_date_visits_apply_the_defaults() {
+        let (url, requests) = serve(&[(200, "[1]"), (200, "[{}]")]).await;
+        let service = service_with_tool(&url, "VisitsSummary", "get")
+            .with_default_site_id("1".to_string())
+            .with_default_period("week".to_string())
+            .with_default_language("fr".to_string());
+
+        let mut arguments = JsonObject::new();
+        arguments.insert("dates".to_string(), serde_json::json!(["2024-11-29"]));
+        service
+            .run_tool("matomo_visits_multi_date", Some(arguments))
+            .await
+            .unwrap();
+        let bulk = &requests.await.unwrap()[1];
+        assert!(bulk.contains("period%3Dweek"), "{}", bulk);
+        assert!(bulk.contains("language%3Dfr"), "{}", bulk);
+    }
+
+    #[tokio::test]
+    async fn test_multi_date_visits_check_the_period() {
+        let service = service_with_tool("http://127.0.0.1:9", "VisitsSummary", "get");
+
+        let mut arguments = JsonObject::new();
+        arguments.insert("idSite".to_string(), serde_json::json!(1));
+        arguments.insert("dates".to_string(), serde_json::json!(["2024-11-29"]));
+        arguments.insert("period".to_string(), serde_json::json!("range"));
+        let err = service
+            .run_tool("matomo_visits_multi_date", Some(arguments))
+            .await
+            .unwrap_err();
+        assert!(
+            err.message.contains("day, week, month, year"),
+            "{}",
+            err.message
+        );
+    }
+
+    #[tokio::test]
+    async fn test_raw_calls_bypass_the_cache() {
+        let (url, requests) = serve(&[(200, "1"), (200, "2")]).await;
+        let service = service_with_tool(&url, "VisitsSummary", "get")
+            .with_response_cache(ResponseCache::new(8, Duration::from_secs(60)));
+
+        for _ in 0..2 {
+            let mut arguments = JsonObject::new();
+            arguments.insert(
+                "method".to_string(),
+                serde_json::json!("API.getMatomoVersion"),
+            );
+            service
+                .run_tool("matomo_call", Some(arguments))
+                .await
+                .unwrap();
         }
+        assert_eq!(requests.await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_raw_calls_refuse_write_methods() {
+        let service = service_with_tool("http://127.0.0.1:9", "VisitsSummary", "get");
+
+        let mut arguments = JsonObject::new();
+        arguments.insert(
+            "method".to_string(),
+            serde_json::json!("ScheduledReports.sendReport"),
+        );
+        let err = service
+            .run_tool("matomo_call", Some(arguments))
+            .await
+            .unwrap_err();
+        assert!(
+            err.message.contains("--allow-write-calls"),
+            "{}",
+            err.message
+        );
+    }
+
+    #[tokio::test]
+    async fn test_call_meta_reports_failed_statuses() {
+        let (url, _requests) = serve(&[(500, "Internal error")]).await;
+        let service = service_with_tool(&url, "VisitsSummary", "get").with_call_meta();
+
+        let result = service.run_tool("VisitsSummary_get", None).await.unwrap();
+        assert_eq!(result.is_error, Some(true));
+        assert_eq!(result.meta.unwrap().0["http_status"], 500);
+    }
+
+    #[tokio::test]
+    async fn test_structured_tools_only_return_json() {
+        let mut service = service_with_tool("http://127.0.0.1:9", "VisitsSummary", "get");
+        Arc::make_mut(&mut service.tools)[0].response_schema = Some(serde_json::json!({
+            "type": "object",
+            "properties": {"nb_visits": {"type": "integer"}}
+        }));
+        let tool = service.tool_to_mcp(&service.tools[0]);
+        assert!(tool.output_schema.is_some());
+        assert!(tool.input_schema["properties"].get("format").is_none());
+
+        let mut arguments = JsonObject::new();
+        arguments.insert("format".to_string(), serde_json::json!("CSV"));
+        let err = service
+            .run_tool("VisitsSummary_get", Some(arguments))
+            .await
+            .unwrap_err();
+        assert!(err.message.contains("format=CSV"), "{}", err.message);
+    }
+
+    #[tokio::test]
+    async fn test_redacted_fields_are_fetched_as_json() {
+        let (url, request) = serve(&[(200, r#"[{"label": "jane@example.com"}]"#)]).await;
+        let service = service_with_tool(&url, "Referrers", "getWebsites")
+            .with_response_redaction(PiiRedactor::new(vec!["label".to_string()]));
+
+        let mut arguments = JsonObject::new();
+        arguments.insert("format".to_string(), serde_json::json!("TSV"));
+        let mut result = service
+            .run_tool("Referrers_getWebsites", Some(arguments))
+            .await
+            .unwrap();
+        redact_result(service.response_redactor.as_ref().unwrap(), &mut result);
+
+        assert!(request.await.unwrap()[0].contains("format=JSON"));
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(!text.contains("jane@example.com"), "{}", text);
     }
 }
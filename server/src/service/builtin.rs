@@ -0,0 +1,1332 @@
+//! Built-in tools implemented by the server rather than derived from the spec
+
+use super::{error_result, MatomoService, SITE_ID_PARAM};
+use crate::dates::{normalize_date, period_synonym};
+use crate::error::{MatomoError, MatomoErrorKind};
+use crate::http_client::AuthMode;
+use crate::matomo_client::{extract_scalar, item_errors};
+use crate::openapi::MatomoTool;
+use crate::parser::{convert_parameter, parse_method_list};
+use crate::processed_report::reshape_processed_report;
+use crate::segment::parse_segment_dimensions;
+use rmcp::model::{CallToolResult, Content, Tool};
+use rmcp::ErrorData;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Connectivity check calling `API.getMatomoVersion`
+pub const PING_TOOL: &str = "matomo_ping";
+
+/// Connection, loaded modules and defaults of this server, without calling Matomo
+pub const SERVER_INFO_TOOL: &str = "matomo_server_info";
+
+/// Call counts, errors and latencies of every tool since startup
+pub const METRICS_TOOL: &str = "matomo_metrics";
+
+/// Fresh report metadata of one method, from `API.getMetadata`
+pub const DESCRIBE_TOOL: &str = "matomo_describe";
+
+/// One report as a labeled table, from `API.getProcessedReport`
+pub const PROCESSED_REPORT_TOOL: &str = "matomo_processed_report";
+
+/// Sites the token can access, from `SitesManager.getSitesWithAtLeastViewAccess`
+pub const LIST_SITES_TOOL: &str = "matomo_list_sites";
+
+/// Saved segments, from `SegmentEditor.getAll`
+pub const LIST_SEGMENTS_TOOL: &str = "matomo_list_segments";
+
+/// Suggested values of a segment dimension, from `API.getSuggestedValuesForSegment`
+pub const SEGMENT_VALUES_TOOL: &str = "matomo_segment_values";
+
+/// Segment syntax and dimension check against `API.getSegmentsMetadata`
+pub const VALIDATE_SEGMENT_TOOL: &str = "matomo_validate_segment";
+
+/// Several tool calls in one `API.getBulkRequest` round trip
+pub const BULK_TOOL: &str = "matomo_bulk";
+
+/// Any Matomo API method, including those absent from report metadata
+pub const CALL_TOOL: &str = "matomo_call";
+
+/// `VisitsSummary.get` for a list of dates, in one bulk request
+pub const VISITS_MULTI_DATE_TOOL: &str = "matomo_visits_multi_date";
+
+/// Most dates `matomo_visits_multi_date` accepts in one call
+const MAX_MULTI_DATES: usize = 100;
+
+/// Direct API URL of a tool call, without calling Matomo
+pub const API_URL_TOOL: &str = "matomo_api_url";
+
+/// Number of rows of a report, fetched with its labels only
+pub const COUNT_ROWS_TOOL: &str = "matomo_count_rows";
+
+/// Build the MCP definition of a built-in tool
+fn builtin_tool(
+    name: &'static str,
+    description: &'static str,
+    input_schema: serde_json::Value,
+) -> Tool {
+    let serde_json::Value::Object(schema) = input_schema else {
+        unreachable!("built-in tool schemas are JSON objects");
+    };
+
+    Tool {
+        name: Cow::Borrowed(name),
+        description: Some(Cow::Borrowed(description)),
+        input_schema: Arc::new(schema),
+        annotations: None,
+        icons: None,
+        meta: None,
+        output_schema: None,
+        title: None,
+    }
+}
+
+/// Render a JSON value as a tool result
+fn json_result(value: serde_json::Value, is_error: bool) -> CallToolResult {
+    let text = serde_json::to_string_pretty(&value).unwrap_or_else(|_| value.to_string());
+    CallToolResult {
+        content: vec![Content::text(text)],
+        is_error: Some(is_error),
+        meta: None,
+        structured_content: None,
+    }
+}
+
+/// Render the result of a Matomo call, reporting failures as tool errors
+fn matomo_result(result: anyhow::Result<serde_json::Value>) -> Result<CallToolResult, ErrorData> {
+    match result {
+        Ok(value) => Ok(json_result(value, false)),
+        Err(e) => error_result(e),
+    }
+}
+
+/// Names of every built-in tool, in `builtin_tools` order
+pub(super) const BUILTIN_TOOLS: [&str; 14] = [
+    PING_TOOL,
+    SERVER_INFO_TOOL,
+    METRICS_TOOL,
+    DESCRIBE_TOOL,
+    PROCESSED_REPORT_TOOL,
+    LIST_SITES_TOOL,
+    LIST_SEGMENTS_TOOL,
+    SEGMENT_VALUES_TOOL,
+    VALIDATE_SEGMENT_TOOL,
+    BULK_TOOL,
+    CALL_TOOL,
+    VISITS_MULTI_DATE_TOOL,
+    API_URL_TOOL,
+    COUNT_ROWS_TOOL,
+];
+
+/// Whether a tool answers without calling Matomo, so it works with `--offline`
+pub(super) fn works_offline(name: &str) -> bool {
+    matches!(name, SERVER_INFO_TOOL | METRICS_TOOL | API_URL_TOOL)
+}
+
+impl MatomoService {
+    /// MCP definitions of the built-in tools
+    pub(super) fn builtin_tools(&self) -> Vec<Tool> {
+        vec![
+            builtin_tool(
+                PING_TOOL,
+                "Check connectivity to the Matomo instance: returns the connection status, \
+                 the configured base URL, the Matomo version and whether the token is accepted",
+                serde_json::json!({"type": "object", "properties": {}}),
+            ),
+            builtin_tool(
+                SERVER_INFO_TOOL,
+                "Describe this server: Matomo base URL and version, the API modules with their \
+                 number of tools, the total tool count and the defaults applied to omitted \
+                 arguments. Use it to see which modules exist before picking report tools",
+                serde_json::json!({"type": "object", "properties": {}}),
+            ),
+            builtin_tool(
+                METRICS_TOOL,
+                "Usage statistics of this server since it started: number of calls, errors, \
+                 average and maximum latency and a latency histogram (milliseconds) per tool",
+                serde_json::json!({"type": "object", "properties": {}}),
+            ),
+            builtin_tool(
+                DESCRIBE_TOOL,
+                "Fetch the current report metadata of one method from Matomo: documentation, \
+                 parameters, metrics with their labels and units, supported formats. Works for \
+                 reports added by plugins installed after the server started",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "method": {
+                            "type": "string",
+                            "description": "Report method as Module.action (e.g. Actions.getPageUrls) or tool name (Actions_getPageUrls)"
+                        },
+                        "idSite": {
+                            "type": "integer",
+                            "description": "Site whose report metadata is read"
+                        }
+                    },
+                    "required": ["method"]
+                }),
+            ),
+            builtin_tool(
+                PROCESSED_REPORT_TOOL,
+                "Fetch a report as a labeled table: its columns with their display names \
+                 (e.g. nb_visits -> Visits), the rows as arrays aligned with the columns, and \
+                 the totals. Prefer it to the report tools when metric ids are not self-explanatory",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "apiModule": {
+                            "type": "string",
+                            "description": "Module of the report, e.g. Actions"
+                        },
+                        "apiAction": {
+                            "type": "string",
+                            "description": "Action of the report, e.g. getPageUrls"
+                        },
+                        "idSite": {
+                            "type": "integer",
+                            "description": "The site ID"
+                        },
+                        "period": {
+                            "type": "string",
+                            "description": "day, week, month, year or range"
+                        },
+                        "date": {
+                            "type": "string",
+                            "description": "Date or range, e.g. yesterday, 2025-01-06, last7"
+                        },
+                        "segment": {
+                            "type": "string",
+                            "description": "Segment expression, e.g. deviceType==desktop"
+                        },
+                        "filter_limit": {
+                            "type": "integer",
+                            "description": "Maximum number of rows"
+                        }
+                    },
+                    "required": ["apiModule", "apiAction"]
+                }),
+            ),
+            builtin_tool(
+                LIST_SITES_TOOL,
+                "List the websites the token can access (idsite, name, main_url, timezone, ...). \
+                 Use it to find the idSite to pass to report tools",
+                serde_json::json!({"type": "object", "properties": {}}),
+            ),
+            builtin_tool(
+                LIST_SEGMENTS_TOOL,
+                "List the segments saved in Matomo (name and definition) for a site, so that \
+                 an analyst's segments can be reused as the 'segment' argument of report tools",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "idSite": {
+                            "type": "integer",
+                            "description": "Site whose segments are listed (segments shared with all sites are included)"
+                        }
+                    }
+                }),
+            ),
+            builtin_tool(
+                SEGMENT_VALUES_TOOL,
+                "List values recently seen for a segment dimension (e.g. deviceType -> \
+                 desktop, smartphone, tablet), to write segment conditions that match data",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "segment": {
+                            "type": "string",
+                            "description": "Segment dimension name, e.g. deviceType or browserCode"
+                        },
+                        "idSite": {
+                            "type": "integer",
+                            "description": "Site whose visits provide the values"
+                        }
+                    },
+                    "required": ["segment"]
+                }),
+            ),
+            builtin_tool(
+                VALIDATE_SEGMENT_TOOL,
+                "Check a segment expression (e.g. deviceType==desktop;visitCount>2) before \
+                 using it: verifies the syntax and that every dimension exists for the site, \
+                 and lists the valid dimension names when it does not",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "segment": {
+                            "type": "string",
+                            "description": "Segment expression: conditions joined by ';' (AND) or ',' (OR)"
+                        },
+                        "idSite": {
+                            "type": "integer",
+                            "description": "Site whose segment dimensions are checked"
+                        }
+                    },
+                    "required": ["segment"]
+                }),
+            ),
+            builtin_tool(
+                BULK_TOOL,
+                "Run several report tool calls in a single Matomo request (API.getBulkRequest), \
+                 e.g. the same report for many dates. Returns the array of results in call order; \
+                 a failed call yields its error object and the result is flagged as an error",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "calls": {
+                            "type": "array",
+                            "minItems": 1,
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "tool": {
+                                        "type": "string",
+                                        "description": "Report tool name, e.g. VisitsSummary_get"
+                                    },
+                                    "arguments": {
+                                        "type": "object",
+                                        "description": "Arguments of the tool"
+                                    }
+                                },
+                                "required": ["tool"]
+                            }
+                        }
+                    },
+                    "required": ["calls"]
+                }),
+            ),
+            builtin_tool(
+                CALL_TOOL,
+                "Call any Matomo API method by name (e.g. SitesManager.getSitesWithAtLeastViewAccess, \
+                 Goals.getGoals), including methods that have no dedicated tool. Parameters are \
+                 passed as-is. Prefer the dedicated report tools when one exists. Only read \
+                 methods (get*, is*, has*, can*, check*, ...) are allowed unless the server \
+                 runs with --allow-write-calls",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "method": {
+                            "type": "string",
+                            "description": "Matomo method as Module.action"
+                        },
+                        "params": {
+                            "type": "object",
+                            "description": "Method parameters, e.g. {\"idSite\": 1}"
+                        }
+                    },
+                    "required": ["method"]
+                }),
+            ),
+            builtin_tool(
+                VISITS_MULTI_DATE_TOOL,
+                "Visits summary (visits, unique visitors, actions, bounce rate, ...) for an \
+                 explicit list of possibly non-contiguous dates, e.g. every Black Friday, in one \
+                 request. Returns the metrics keyed by date; dates that failed are listed with \
+                 their error",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "idSite": {
+                            "type": "integer",
+                            "description": "The site ID"
+                        },
+                        "dates": {
+                            "type": "array",
+                            "items": {"type": "string"},
+                            "minItems": 1,
+                            "maxItems": MAX_MULTI_DATES,
+                            "description": "Dates as YYYY-MM-DD (or today, yesterday)"
+                        },
+                        "period": {
+                            "type": "string",
+                            "enum": MULTI_DATE_PERIODS,
+                            "description": "Period around each date; defaults to the server's \
+                                            default period, or day"
+                        },
+                        "segment": {
+                            "type": "string",
+                            "description": "Segment expression, e.g. deviceType==desktop"
+                        }
+                    },
+                    "required": ["dates"]
+                }),
+            ),
+            builtin_tool(
+                API_URL_TOOL,
+                "Build the Matomo API URL a report tool call maps to, to open it in a browser \
+                 or use it in a cron job or spreadsheet. The arguments are validated like a real \
+                 call, defaults included; the token is left as a {token_auth} placeholder",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "tool": {
+                            "type": "string",
+                            "description": "Report tool name, e.g. VisitsSummary_get"
+                        },
+                        "arguments": {
+                            "type": "object",
+                            "description": "Arguments of the tool"
+                        }
+                    },
+                    "required": ["tool"]
+                }),
+            ),
+            builtin_tool(
+                COUNT_ROWS_TOOL,
+                "Count the rows of a report tool (e.g. how many page URLs or referrers) without \
+                 returning them. Hierarchical reports count their top-level rows, with \
+                 rows_with_subtable telling how many have sub-rows; pass flat=true in the \
+                 arguments to count every leaf instead. Multi-period dates return one count per period",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "tool": {
+                            "type": "string",
+                            "description": "Report tool name, e.g. Actions_getPageUrls"
+                        },
+                        "arguments": {
+                            "type": "object",
+                            "description": "Arguments of the tool (filter_limit and filter_offset are ignored)"
+                        }
+                    },
+                    "required": ["tool"]
+                }),
+            ),
+        ]
+    }
+
+    /// Run a built-in tool, or return `None` if `name` is not one
+    pub(super) async fn call_builtin(
+        &self,
+        name: &str,
+        params: &HashMap<String, serde_json::Value>,
+    ) -> Option<Result<CallToolResult, ErrorData>> {
+        match name {
+            PING_TOOL => Some(Ok(self.ping().await)),
+            SERVER_INFO_TOOL => Some(Ok(json_result(self.server_info(), false))),
+            METRICS_TOOL => Some(Ok(json_result(self.metrics.to_json(), false))),
+            PROCESSED_REPORT_TOOL => Some(self.processed_report(params).await),
+            LIST_SITES_TOOL => Some(matomo_result(
+                self.client
+                    .call_method(
+                        "SitesManager",
+                        "getSitesWithAtLeastViewAccess",
+                        HashMap::new(),
+                    )
+                    .await,
+            )),
+            LIST_SEGMENTS_TOOL => Some(self.list_segments(params).await),
+            DESCRIBE_TOOL => Some(self.describe(params).await),
+            SEGMENT_VALUES_TOOL => Some(self.segment_values(params).await),
+            VALIDATE_SEGMENT_TOOL => Some(self.validate_segment(params).await),
+            BULK_TOOL => Some(self.bulk(params).await),
+            CALL_TOOL => Some(self.call_raw(params).await),
+            VISITS_MULTI_DATE_TOOL => Some(self.visits_multi_date(params).await),
+            API_URL_TOOL => Some(self.api_url(params)),
+            COUNT_ROWS_TOOL => Some(self.count_rows(params).await),
+            _ => None,
+        }
+    }
+
+    /// Validate every call, then send them all in one bulk request
+    async fn bulk(
+        &self,
+        params: &HashMap<String, serde_json::Value>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let calls = params
+            .get("calls")
+            .and_then(|v| v.as_array())
+            .filter(|calls| !calls.is_empty())
+            .ok_or_else(|| ErrorData::invalid_params("'calls' must be a non-empty array", None))?;
+
+        let mut prepared = Vec::with_capacity(calls.len());
+        let mut modules = Vec::with_capacity(calls.len());
+        for (index, call) in calls.iter().enumerate() {
+            let invalid = |message: String| {
+                ErrorData::invalid_params(format!("calls[{}]: {}", index, message), None)
+            };
+
+            let name = call
+                .get("tool")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| invalid("missing 'tool'".to_string()))?;
+            let tool = self.resolve_tool(name).map_err(invalid)?;
+
+            let mut args: HashMap<String, serde_json::Value> = call
+                .get("arguments")
+                .and_then(|v| v.as_object())
+                .map(|obj| obj.clone().into_iter().collect())
+                .unwrap_or_default();
+            self.prepare_arguments(tool, &mut args).map_err(invalid)?;
+            self.check_site_access(&args).await?;
+
+            prepared.push((tool.module.clone(), tool.action.clone(), args));
+            modules.push(tool.module.clone());
+        }
+
+        match self.client.call_bulk(prepared).await {
+            Ok(mut results) => {
+                if let serde_json::Value::Array(ref mut items) = results {
+                    for (item, module) in items.iter_mut().zip(&modules) {
+                        if self.pii_scope.applies_to(module) {
+                            self.pii_redactor.redact(item);
+                        }
+                    }
+                }
+
+                // A partial failure is an error, with the successful results kept
+                let failures: Vec<String> = item_errors(&results)
+                    .into_iter()
+                    .map(|(index, message)| {
+                        let tool = calls[index].get("tool").and_then(|v| v.as_str());
+                        format!("calls[{}] ({}): {}", index, tool.unwrap_or("?"), message)
+                    })
+                    .collect();
+                let mut result = json_result(results, !failures.is_empty());
+                if !failures.is_empty() {
+                    result.content.push(Content::text(format!(
+                        "{} of {} calls failed:\n{}",
+                        failures.len(),
+                        calls.len(),
+                        failures.join("\n")
+                    )));
+                }
+                Ok(result)
+            }
+            Err(e) => matomo_result(Err(e)),
+        }
+    }
+
+    /// Call an arbitrary `Module.action` method with free-form parameters
+    async fn call_raw(
+        &self,
+        params: &HashMap<String, serde_json::Value>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let method = params
+            .get("method")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| {
+                ErrorData::invalid_params("Missing required parameter 'method'", None)
+            })?;
+        let (module, action) = split_method(method).ok_or_else(|| {
+            ErrorData::invalid_params(
+                format!("Invalid method '{}', expected Module.action", method),
+                None,
+            )
+        })?;
+        if !is_read_method(module, action) && !self.allow_write_calls {
+            return Err(ErrorData::invalid_params(
+                format!(
+                    "{} is not a read method ({}*); the server must run with \
+                     --allow-write-calls to call it",
+                    method,
+                    READ_ACTION_PREFIXES.join("*, ")
+                ),
+                None,
+            ));
+        }
+        let mut args: HashMap<String, serde_json::Value> = match params.get("params") {
+            None | Some(serde_json::Value::Null) => HashMap::new(),
+            Some(serde_json::Value::Object(obj)) => obj.clone().into_iter().collect(),
+            Some(_) => {
+                return Err(ErrorData::invalid_params(
+                    "'params' must be an object",
+                    None,
+                ))
+            }
+        };
+
+        self.check_site_access(&args).await?;
+        let note = self.force_json_if_masked(module, &mut args);
+
+        // Not cached: a repeated call may be a write meant to run again
+        let mut result = self.client.call_method_uncached(module, action, args).await;
+        if let Ok(ref mut value) = result {
+            if self.pii_scope.applies_to(module) {
+                self.pii_redactor.redact(value);
+            }
+        }
+        matomo_result(result).map(|mut result| {
+            result
+                .content
+                .extend(note.map(|note| Content::text(format!("Note: {}", note))));
+            result
+        })
+    }
+
+    /// Call `API.getProcessedReport` and reshape it into columns, rows and totals
+    ///
+    /// Arguments besides the report name are passed through, after the
+    /// server defaults; a response that cannot be reshaped is returned as is.
+    async fn processed_report(
+        &self,
+        params: &HashMap<String, serde_json::Value>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let mut args = params.clone();
+        let module = params
+            .get("apiModule")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| {
+                ErrorData::invalid_params("Missing required parameter 'apiModule'", None)
+            })?;
+        if !params.get("apiAction").is_some_and(|v| v.is_string()) {
+            return Err(ErrorData::invalid_params(
+                "Missing required parameter 'apiAction'",
+                None,
+            ));
+        }
+        for (name, value) in self.parameter_defaults() {
+            args.entry(name.to_string()).or_insert_with(|| value.into());
+        }
+        // The reshaping needs the JSON response
+        args.remove("format");
+        self.check_site_access(&args).await?;
+
+        let mut report = match self
+            .client
+            .call_method("API", "getProcessedReport", args)
+            .await
+        {
+            Ok(report) => report,
+            Err(e) => return error_result(e),
+        };
+        if self.pii_scope.applies_to(module) {
+            self.pii_redactor.redact(&mut report);
+        }
+        let table = reshape_processed_report(&report).unwrap_or(report);
+        Ok(json_result(table, false))
+    }
+
+    /// One `VisitsSummary.get` per date, sent as a single bulk request
+    async fn visits_multi_date(
+        &self,
+        params: &HashMap<String, serde_json::Value>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let dates = params
+            .get("dates")
+            .and_then(|v| v.as_array())
+            .filter(|dates| (1..=MAX_MULTI_DATES).contains(&dates.len()))
+            .ok_or_else(|| {
+                ErrorData::invalid_params(
+                    format!("'dates' must be an array of 1 to {} dates", MAX_MULTI_DATES),
+                    None,
+                )
+            })?;
+        let dates: Vec<String> = dates
+            .iter()
+            .map(|date| {
+                let date = date.as_str().filter(|d| !d.contains(',')).ok_or_else(|| {
+                    ErrorData::invalid_params(
+                        format!("Invalid date {}: expected a single date per entry", date),
+                        None,
+                    )
+                })?;
+                normalize_date(date).map_err(|e| ErrorData::invalid_params(e, None))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let mut args: HashMap<String, serde_json::Value> = HashMap::new();
+        for name in [SITE_ID_PARAM, "period", "segment"] {
+            if let Some(value) = params.get(name).filter(|v| !v.is_null()) {
+                args.insert(name.to_string(), value.clone());
+            }
+        }
+        let period = match args.get("period") {
+            Some(period) => multi_date_period(period)?,
+            // A default period such as range does not apply to single dates
+            None => self
+                .default_period
+                .as_deref()
+                .filter(|period| MULTI_DATE_PERIODS.contains(period))
+                .unwrap_or("day"),
+        };
+        args.insert("period".to_string(), period.into());
+        for (name, value) in self
+            .parameter_defaults()
+            .filter(|(name, _)| *name != "date")
+        {
+            args.entry(name.to_string()).or_insert_with(|| value.into());
+        }
+        if !args.contains_key(SITE_ID_PARAM) {
+            return Err(ErrorData::invalid_params(
+                "Missing required parameter 'idSite'",
+                None,
+            ));
+        }
+        self.check_site_access(&args).await?;
+
+        let calls = dates
+            .iter()
+            .map(|date| {
+                let mut args = args.clone();
+                args.insert("date".to_string(), date.as_str().into());
+                ("VisitsSummary".to_string(), "get".to_string(), args)
+            })
+            .collect();
+        let results = match self.client.call_bulk(calls).await {
+            Ok(results) => results,
+            Err(e) => return error_result(e),
+        };
+
+        let (by_date, failures) = merge_by_date(&dates, results);
+        let failed = !failures.is_empty();
+        let mut value = serde_json::json!({ "dates": by_date });
+        if failed {
+            value["errors"] = serde_json::Value::Object(failures);
+        }
+        Ok(json_result(value, failed))
+    }
+
+    /// URL of a spec tool call, after the same defaults and validation
+    fn api_url(
+        &self,
+        params: &HashMap<String, serde_json::Value>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let (tool, args) = self.tool_call(params)?;
+
+        let url = self.client.api_url(&tool.module, &tool.action, args);
+        let note = match self.client.auth_mode() {
+            AuthMode::TokenAuthForm => {
+                "Replace {token_auth} with an API token; prefer a token with view access only"
+            }
+            AuthMode::BearerHeader => {
+                "Send the token in an 'Authorization: Bearer <token>' header; prefer a token \
+                 with view access only"
+            }
+        };
+        Ok(json_result(
+            serde_json::json!({
+                "tool": tool.name,
+                "url": url,
+                "note": note,
+            }),
+            false,
+        ))
+    }
+
+    /// Fetch a report with every row but only its labels, and count the rows
+    async fn count_rows(
+        &self,
+        params: &HashMap<String, serde_json::Value>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let (tool, mut args) = self.tool_call(params)?;
+        self.check_site_access(&args).await?;
+        args.insert("filter_limit".to_string(), (-1).into());
+        args.insert("showColumns".to_string(), "label".into());
+        for name in ["filter_offset", "format"] {
+            args.remove(name);
+        }
+
+        let report = match self
+            .client
+            .call_method(&tool.module, &tool.action, args)
+            .await
+        {
+            Ok(report) => report,
+            Err(e) => return error_result(e),
+        };
+        match row_counts(&report) {
+            Some(counts) => {
+                let mut value = serde_json::json!({ "tool": tool.name });
+                if let serde_json::Value::Object(counts) = counts {
+                    value.as_object_mut().unwrap().extend(counts);
+                }
+                Ok(json_result(value, false))
+            }
+            None => Err(ErrorData::invalid_params(
+                format!(
+                    "{} does not return a list of rows; call it directly instead",
+                    tool.name
+                ),
+                None,
+            )),
+        }
+    }
+
+    /// Spec tool and prepared arguments of a `{tool, arguments}` request
+    ///
+    /// The arguments get the same defaults and validation as a real call.
+    fn tool_call(
+        &self,
+        params: &HashMap<String, serde_json::Value>,
+    ) -> Result<(&MatomoTool, HashMap<String, serde_json::Value>), ErrorData> {
+        let name = params
+            .get("tool")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ErrorData::invalid_params("Missing required parameter 'tool'", None))?;
+        let tool = self
+            .resolve_tool(name)
+            .map_err(|e| ErrorData::invalid_params(e, None))?;
+        let mut args: HashMap<String, serde_json::Value> = match params.get("arguments") {
+            None | Some(serde_json::Value::Null) => HashMap::new(),
+            Some(serde_json::Value::Object(obj)) => obj.clone().into_iter().collect(),
+            Some(_) => {
+                return Err(ErrorData::invalid_params(
+                    "'arguments' must be an object",
+                    None,
+                ))
+            }
+        };
+        self.prepare_arguments(tool, &mut args)
+            .map_err(|e| ErrorData::invalid_params(e, None))?;
+        Ok((tool, args))
+    }
+
+    /// List saved segments with their name and definition
+    ///
+    /// Reading segments needs view access; a token without it gets an
+    /// explanation instead of a protocol error.
+    async fn list_segments(
+        &self,
+        params: &HashMap<String, serde_json::Value>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let mut args = HashMap::new();
+        match params.get(SITE_ID_PARAM) {
+            Some(id_site @ (serde_json::Value::String(_) | serde_json::Value::Number(_))) => {
+                args.insert("idSite".to_string(), id_site.clone());
+            }
+            _ => {
+                if let Some(ref site_id) = self.default_site_id {
+                    args.insert("idSite".to_string(), site_id.clone().into());
+                }
+            }
+        }
+
+        match self
+            .client
+            .call_method("SegmentEditor", "getAll", args)
+            .await
+        {
+            Ok(serde_json::Value::Array(segments)) => {
+                let segments: Vec<serde_json::Value> = segments
+                    .iter()
+                    .map(|segment| {
+                        serde_json::json!({
+                            "name": segment.get("name"),
+                            "definition": segment.get("definition"),
+                            "idsegment": segment.get("idsegment"),
+                            "enable_only_idsite": segment.get("enable_only_idsite"),
+                        })
+                    })
+                    .collect();
+                Ok(json_result(serde_json::json!(segments), false))
+            }
+            Ok(other) => Ok(json_result(other, false)),
+            Err(e) if is_access_error(&e) => Ok(json_result(
+                serde_json::json!({
+                    "segments": [],
+                    "error": format!("{:#}", e),
+                    "hint": "The token cannot read saved segments; write the segment \
+                             expression by hand and check it with matomo_validate_segment",
+                }),
+                true,
+            )),
+            Err(e) => matomo_result(Err(e)),
+        }
+    }
+
+    /// Report metadata of one method, parsed like at introspection
+    async fn describe(
+        &self,
+        params: &HashMap<String, serde_json::Value>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let method = params
+            .get("method")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| {
+                ErrorData::invalid_params("Missing required parameter 'method'", None)
+            })?;
+        let (module, action) = match split_method(method) {
+            Some((module, action)) => (module.to_string(), action.to_string()),
+            None => {
+                let tool = self.resolve_tool(method).map_err(|_| {
+                    ErrorData::invalid_params(
+                        format!(
+                            "'{}' is neither Module.action nor a tool name, e.g. \
+                             Actions.getPageUrls",
+                            method
+                        ),
+                        None,
+                    )
+                })?;
+                (tool.module.clone(), tool.action.clone())
+            }
+        };
+        let id_site = match params.get(SITE_ID_PARAM) {
+            Some(serde_json::Value::String(s)) => Some(s.clone()),
+            Some(serde_json::Value::Number(n)) => Some(n.to_string()),
+            _ => self.default_site_id.clone(),
+        }
+        .ok_or_else(|| ErrorData::invalid_params("Missing required parameter 'idSite'", None))?;
+
+        let metadata = match self
+            .client
+            .report_metadata(&module, &action, &id_site)
+            .await
+        {
+            Ok(metadata) => metadata,
+            Err(e) => return matomo_result(Err(e)),
+        };
+        let parsed = parse_method_list(&metadata)
+            .map_err(|e| ErrorData::internal_error(format!("{:#}", e), None))?;
+        let tool_name = format!("{}_{}", module, action);
+        let tool = self.find_tool(&tool_name).map(|tool| tool.name.clone());
+
+        let Some(report) = parsed
+            .into_iter()
+            .find(|report| report.module == module && report.action == action)
+        else {
+            return Ok(json_result(
+                serde_json::json!({
+                    "method": format!("{}.{}", module, action),
+                    "tool": tool,
+                    "error": format!(
+                        "Matomo has no report metadata for {}.{} on site {}: it is not a \
+                         report, its plugin is not activated, or the token cannot view the site",
+                        module, action, id_site
+                    ),
+                }),
+                true,
+            ));
+        };
+
+        let parameters: Vec<serde_json::Value> = report
+            .parameters
+            .iter()
+            .map(|param| {
+                let (param_type, _) = convert_parameter(param).param_type.to_openapi_type();
+                serde_json::json!({
+                    "name": param.name,
+                    "type": param_type,
+                    "required": param.required,
+                    "default": param.default,
+                    "known_values": param.known_values,
+                })
+            })
+            .collect();
+        let metrics: serde_json::Map<String, serde_json::Value> = report
+            .metrics
+            .iter()
+            .map(|(id, label)| {
+                let mut metric = serde_json::json!({"label": label});
+                if let Some(doc) = report.metrics_documentation.get(id) {
+                    metric["documentation"] = doc.clone().into();
+                }
+                if let Some(unit) = report.metric_units.get(id) {
+                    metric["unit"] = unit.clone().into();
+                }
+                (id.clone(), metric)
+            })
+            .collect();
+
+        Ok(json_result(
+            serde_json::json!({
+                "method": format!("{}.{}", module, action),
+                // null when the method appeared after startup: use matomo_call
+                "tool": tool,
+                "name": report.name,
+                "category": report.category,
+                "documentation": report.documentation,
+                "parameters": parameters,
+                "metrics": metrics,
+                "supported_formats": report.supported_formats,
+                "has_dimension": report.has_dimension,
+                "hierarchical": report.has_subtables,
+                "deprecated": report.deprecated,
+            }),
+            false,
+        ))
+    }
+
+    /// Suggested values for a segment dimension
+    async fn segment_values(
+        &self,
+        params: &HashMap<String, serde_json::Value>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let segment = params
+            .get("segment")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| {
+                ErrorData::invalid_params("Missing required parameter 'segment'", None)
+            })?;
+        let id_site = match params.get(SITE_ID_PARAM) {
+            Some(serde_json::Value::String(s)) => Some(s.clone()),
+            Some(serde_json::Value::Number(n)) => Some(n.to_string()),
+            _ => self.default_site_id.clone(),
+        }
+        .ok_or_else(|| ErrorData::invalid_params("Missing required parameter 'idSite'", None))?;
+
+        match self.client.segment_values(segment, &id_site).await {
+            Ok(values) if values.is_empty() => Ok(json_result(
+                serde_json::json!({
+                    "segment": segment,
+                    "values": [],
+                    "note": "Matomo has no suggested values for this dimension yet \
+                             (no recent visits with it, or browser archiving is disabled)",
+                }),
+                false,
+            )),
+            Ok(values) => Ok(json_result(
+                serde_json::json!({"segment": segment, "values": values}),
+                false,
+            )),
+            Err(e) => matomo_result(Err(e)),
+        }
+    }
+
+    /// Check a segment's syntax and dimensions
+    async fn validate_segment(
+        &self,
+        params: &HashMap<String, serde_json::Value>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let segment = params
+            .get("segment")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| {
+                ErrorData::invalid_params("Missing required parameter 'segment'", None)
+            })?;
+        let id_site = match params.get(SITE_ID_PARAM) {
+            Some(serde_json::Value::String(s)) => Some(s.clone()),
+            Some(serde_json::Value::Number(n)) => Some(n.to_string()),
+            _ => self.default_site_id.clone(),
+        };
+
+        let used = match parse_segment_dimensions(segment) {
+            Ok(used) => used,
+            Err(e) => {
+                return Ok(json_result(
+                    serde_json::json!({"valid": false, "segment": segment, "error": e}),
+                    false,
+                ))
+            }
+        };
+
+        let available = match self.client.segment_dimensions(id_site.as_deref()).await {
+            Ok(available) => available,
+            Err(e) => return matomo_result(Err(e)),
+        };
+        let unknown: Vec<&String> = used.iter().filter(|d| !available.contains(d)).collect();
+
+        let report = if unknown.is_empty() {
+            serde_json::json!({"valid": true, "segment": segment, "dimensions": used})
+        } else {
+            serde_json::json!({
+                "valid": false,
+                "segment": segment,
+                "unknown_dimensions": unknown,
+                "valid_dimensions": available,
+            })
+        };
+        Ok(json_result(report, false))
+    }
+
+    /// Summary of the connection and of the loaded tools, grouped by module
+    fn server_info(&self) -> serde_json::Value {
+        let mut modules: BTreeMap<&str, usize> = BTreeMap::new();
+        for tool in self.tools.iter() {
+            *modules.entry(tool.module.as_str()).or_default() += 1;
+        }
+        let defaults: serde_json::Map<String, serde_json::Value> = self
+            .parameter_defaults()
+            .map(|(name, value)| (name.to_string(), value.into()))
+            .collect();
+
+        let mut info = serde_json::json!({
+            "base_url": self.matomo_url,
+            "matomo_version": self.matomo_version,
+            "server_version": env!("CARGO_PKG_VERSION"),
+            "default_site_id": self.default_site_id,
+            "defaults": defaults,
+            "tool_count": self.tools.len() + self.builtin_tools().len(),
+            "module_count": modules.len(),
+            "modules": modules,
+        });
+        if self.offline {
+            info["offline"] = true.into();
+        }
+        if !self.instances.is_empty() {
+            info["instances"] = serde_json::json!(self.instance_names());
+            info["default_instance"] = serde_json::json!(self.default_instance);
+        }
+        info
+    }
+
+    /// Call `API.getMatomoVersion` and report what worked
+    ///
+    /// The method requires view access, so an access error answered by Matomo
+    /// means the instance is reachable but the token was rejected, while a
+    /// transport error means the instance could not be reached at all.
+    async fn ping(&self) -> CallToolResult {
+        let result = self
+            .client
+            .call_method_uncached("API", "getMatomoVersion", HashMap::new())
+            .await;
+
+        match result {
+            Ok(value) => {
+                let version = extract_scalar(&value).map_or(value, serde_json::Value::String);
+                json_result(
+                    serde_json::json!({
+                        "status": "ok",
+                        "base_url": self.matomo_url,
+                        "version": version,
+                        "token_accepted": true,
+                    }),
+                    false,
+                )
+            }
+            Err(e) => {
+                let message = format!("{:#}", e);
+                let (status, token_accepted) = if e.downcast_ref::<reqwest::Error>().is_some() {
+                    ("unreachable", serde_json::Value::Null)
+                } else if is_access_error(&e) {
+                    ("unauthorized", serde_json::Value::Bool(false))
+                } else {
+                    ("error", serde_json::Value::Null)
+                };
+                json_result(
+                    serde_json::json!({
+                        "status": status,
+                        "base_url": self.matomo_url,
+                        "version": null,
+                        "token_accepted": token_accepted,
+                        "error": message,
+                    }),
+                    true,
+                )
+            }
+        }
+    }
+}
+
+/// Key the results of per-date bulk calls by their date
+///
+/// Returns the results of the successful dates and the error message of the
+/// failed ones. A bare JSON value stands for the metrics of its date.
+fn merge_by_date(
+    dates: &[String],
+    results: serde_json::Value,
+) -> (
+    serde_json::Map<String, serde_json::Value>,
+    serde_json::Map<String, serde_json::Value>,
+) {
+    let errors: HashMap<usize, String> = item_errors(&results).into_iter().collect();
+    let results = match results {
+        serde_json::Value::Array(results) => results,
+        other => vec![other],
+    };
+
+    let mut by_date = serde_json::Map::new();
+    let mut failures = serde_json::Map::new();
+    for (index, (date, result)) in dates.iter().zip(results).enumerate() {
+        match errors.get(&index) {
+            Some(message) => failures.insert(date.clone(), message.clone().into()),
+            None => by_date.insert(date.clone(), result),
+        };
+    }
+    (by_date, failures)
+}
+
+/// `{rows, rows_with_subtable}` of a report response
+///
+/// Multi-period responses, keyed by date, give `{periods: {date: counts}}`.
+/// Returns `None` for responses that are not lists of rows (e.g. the single
+/// row of `VisitsSummary.get`).
+fn row_counts(report: &serde_json::Value) -> Option<serde_json::Value> {
+    let count = |rows: &[serde_json::Value]| {
+        serde_json::json!({
+            "rows": rows.len(),
+            "rows_with_subtable": rows
+                .iter()
+                .filter(|row| row.get("idsubdatatable").is_some())
+                .count(),
+        })
+    };
+    match report {
+        serde_json::Value::Array(rows) => Some(count(rows)),
+        serde_json::Value::Object(periods)
+            if !periods.is_empty() && periods.values().all(|rows| rows.is_array()) =>
+        {
+            let periods: serde_json::Map<String, serde_json::Value> = periods
+                .iter()
+                .map(|(date, rows)| Some((date.clone(), count(rows.as_array()?))))
+                .collect::<Option<_>>()?;
+            Some(serde_json::json!({ "periods": periods }))
+        }
+        _ => None,
+    }
+}
+
+/// Whether a Matomo error is about authentication or permissions
+fn is_access_error(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<MatomoError>().is_some_and(|error| {
+        matches!(
+            error.kind,
+            MatomoErrorKind::AuthRequired | MatomoErrorKind::NoAccess
+        )
+    })
+}
+
+/// Split `Module.action`, rejecting anything that is not two identifiers
+fn split_method(method: &str) -> Option<(&str, &str)> {
+    let (module, action) = method.trim().split_once('.')?;
+    let is_identifier =
+        |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    (is_identifier(module) && is_identifier(action)).then_some((module, action))
+}
+
+/// Periods `matomo_visits_multi_date` accepts around each date
+const MULTI_DATE_PERIODS: [&str; 4] = ["day", "week", "month", "year"];
+
+/// Canonical multi-date period of a `period` argument, synonyms such as
+/// `monthly` included
+fn multi_date_period(period: &serde_json::Value) -> Result<&'static str, ErrorData> {
+    period
+        .as_str()
+        .and_then(|period| {
+            let period = period_synonym(period).unwrap_or(period);
+            MULTI_DATE_PERIODS
+                .into_iter()
+                .find(|p| p.eq_ignore_ascii_case(period.trim()))
+        })
+        .ok_or_else(|| {
+            ErrorData::invalid_params(
+                format!(
+                    "Invalid period {}: expected one of {}",
+                    period,
+                    MULTI_DATE_PERIODS.join(", ")
+                ),
+                None,
+            )
+        })
+}
+
+/// Prefixes of the actions that only read from Matomo
+const READ_ACTION_PREFIXES: &[&str] =
+    &["get", "is", "has", "can", "check", "list", "find", "search"];
+
+/// Whether a method only reads from Matomo, e.g. `getSitesIdWithAtLeastViewAccess`
+///
+/// An allowlist, since the actions changing data go by many names (`create*`,
+/// `save*`, `send*`, `run*`, ...). `API.getBulkRequest` is excluded as its
+/// sub-requests may call any method.
+fn is_read_method(module: &str, action: &str) -> bool {
+    if module == "API" && action == "getBulkRequest" {
+        return false;
+    }
+    READ_ACTION_PREFIXES.iter().any(|prefix| {
+        action
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.chars().next().is_none_or(|c| c.is_ascii_uppercase()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_by_date() {
+        let dates = vec!["2024-11-29".to_string(), "2025-11-28".to_string()];
+        let results = serde_json::json!([
+            {"nb_visits": 120, "nb_actions": 300},
+            {"result": "error", "message": "Date is in the future"}
+        ]);
+
+        let (by_date, failures) = merge_by_date(&dates, results);
+        assert_eq!(by_date["2024-11-29"]["nb_visits"], 120);
+        assert!(!by_date.contains_key("2025-11-28"));
+        assert_eq!(failures["2025-11-28"], "Date is in the future");
+    }
+
+    #[test]
+    fn test_row_counts() {
+        let report = serde_json::json!([
+            {"label": "blog", "idsubdatatable": 4},
+            {"label": "/index"},
+            {"label": "/pricing"}
+        ]);
+        assert_eq!(
+            row_counts(&report),
+            Some(serde_json::json!({"rows": 3, "rows_with_subtable": 1}))
+        );
+
+        let report = serde_json::json!({"2025-01-05": [{"label": "/"}], "2025-01-06": []});
+        assert_eq!(
+            row_counts(&report).unwrap()["periods"]["2025-01-06"]["rows"],
+            0
+        );
+        assert_eq!(row_counts(&serde_json::json!({"nb_visits": 3})), None);
+    }
+
+    #[test]
+    fn test_split_method() {
+        assert_eq!(
+            split_method("SitesManager.getAllSites"),
+            Some(("SitesManager", "getAllSites"))
+        );
+        assert_eq!(split_method("SitesManager"), None);
+        assert_eq!(split_method("SitesManager.get.all"), None);
+        assert_eq!(split_method(".getAllSites"), None);
+    }
+
+    #[test]
+    fn test_multi_date_period() {
+        assert_eq!(
+            multi_date_period(&serde_json::json!("Monthly")).unwrap(),
+            "month"
+        );
+        assert_eq!(
+            multi_date_period(&serde_json::json!("week")).unwrap(),
+            "week"
+        );
+        assert!(multi_date_period(&serde_json::json!("range")).is_err());
+        assert!(multi_date_period(&serde_json::json!(7)).is_err());
+    }
+
+    #[test]
+    fn test_only_read_methods_are_allowed() {
+        for (module, action) in [
+            ("SitesManager", "getSitesWithAtLeastViewAccess"),
+            ("UsersManager", "hasSuperUserAccess"),
+            ("Goals", "getGoals"),
+            ("API", "isPluginActivated"),
+        ] {
+            assert!(is_read_method(module, action), "{}.{}", module, action);
+        }
+        for (module, action) in [
+            ("UsersManager", "createAppSpecificTokenAuth"),
+            ("Dashboard", "createNewDashboardForUser"),
+            ("Annotations", "save"),
+            ("ScheduledReports", "sendReport"),
+            ("UsersManager", "inviteUser"),
+            ("CoreAdminHome", "runScheduledTasks"),
+            ("SitesManager", "addSite"),
+            ("UsersManager", "setUserAccess"),
+            ("SitesManager", "getterBypass"),
+            ("API", "getBulkRequest"),
+        ] {
+            assert!(!is_read_method(module, action), "{}.{}", module, action);
+        }
+    }
+
+    #[test]
+    fn test_is_access_error() {
+        let error = |e: MatomoError| anyhow::Error::from(e);
+        assert!(is_access_error(&error(MatomoError::from_message(
+            "You can't access this resource as it requires 'view' access"
+        ))));
+        assert!(is_access_error(&error(MatomoError::from_status(
+            reqwest::StatusCode::UNAUTHORIZED,
+            ""
+        ))));
+        assert!(!is_access_error(&error(MatomoError::from_status(
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+            "oops"
+        ))));
+        assert!(!is_access_error(&anyhow::anyhow!("token_auth missing")));
+    }
+}
@@ -0,0 +1,88 @@
+//! Tracking of running tool calls, so shutdown can wait for them
+//!
+//! Once draining starts, new calls are refused while the running ones, such
+//! as long range reports, get a chance to finish before the transport closes.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Number of running tool calls, shared by the clones of the service
+#[derive(Debug)]
+pub struct InFlight {
+    running: watch::Sender<usize>,
+    draining: AtomicBool,
+}
+
+impl Default for InFlight {
+    fn default() -> Self {
+        Self {
+            running: watch::Sender::new(0),
+            draining: AtomicBool::new(false),
+        }
+    }
+}
+
+/// A running call; finishing it (dropping the guard) updates the count
+pub struct CallGuard(Arc<InFlight>);
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        self.0.running.send_modify(|running| *running -= 1);
+    }
+}
+
+impl InFlight {
+    /// Register a new call, or `None` once the server is draining
+    pub fn start(self: &Arc<Self>) -> Option<CallGuard> {
+        // Count first, so a drain that starts meanwhile waits for this call
+        self.running.send_modify(|running| *running += 1);
+        let guard = CallGuard(Arc::clone(self));
+        (!self.draining.load(Ordering::SeqCst)).then_some(guard)
+    }
+
+    /// Refuse new calls and wait up to `timeout` for the running ones
+    ///
+    /// Returns the number of calls still running when it gives up.
+    pub async fn drain(&self, timeout: Duration) -> usize {
+        self.draining.store(true, Ordering::SeqCst);
+        let mut running = self.running.subscribe();
+        let _ = tokio::time::timeout(timeout, running.wait_for(|&running| running == 0)).await;
+        self.running()
+    }
+
+    /// Number of calls currently running
+    pub fn running(&self) -> usize {
+        *self.running.borrow()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain_waits_for_running_calls_and_refuses_new_ones() {
+        let in_flight = Arc::new(InFlight::default());
+        let call = in_flight.start().unwrap();
+        assert_eq!(in_flight.running(), 1);
+
+        let finish = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            drop(call);
+        });
+        assert_eq!(in_flight.drain(Duration::from_secs(5)).await, 0);
+        finish.await.unwrap();
+
+        assert!(in_flight.start().is_none());
+        assert_eq!(in_flight.running(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_drain_gives_up_after_the_timeout() {
+        let in_flight = Arc::new(InFlight::default());
+        let _call = in_flight.start().unwrap();
+        assert_eq!(in_flight.drain(Duration::from_millis(10)).await, 1);
+    }
+}
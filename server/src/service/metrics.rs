@@ -0,0 +1,149 @@
+//! Per-tool call counters and latency histograms
+//!
+//! Shows which reports a model actually relies on, how often they fail and
+//! how long Matomo takes to answer them. Exposed by the `matomo_metrics` tool
+//! and logged when the server stops.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Upper bounds (inclusive, in milliseconds) of the latency histogram buckets
+const LATENCY_BUCKETS_MS: &[u64] = &[50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000];
+
+/// Counters of one tool
+#[derive(Debug, Default, Clone)]
+struct ToolStats {
+    calls: u64,
+    errors: u64,
+    total: Duration,
+    max: Duration,
+    /// One count per `LATENCY_BUCKETS_MS` entry, plus one for slower calls
+    buckets: [u64; LATENCY_BUCKETS_MS.len() + 1],
+}
+
+/// Call statistics of every tool, shared by the clones of the service
+#[derive(Debug)]
+pub struct Metrics {
+    started: Instant,
+    tools: Mutex<BTreeMap<String, ToolStats>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            tools: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl Metrics {
+    /// Count a finished call of `tool`
+    pub fn record(&self, tool: &str, failed: bool, elapsed: Duration) {
+        let mut tools = self.tools.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = tools.entry(tool.to_string()).or_default();
+        stats.calls += 1;
+        stats.errors += u64::from(failed);
+        stats.total += elapsed;
+        stats.max = stats.max.max(elapsed);
+
+        let ms = elapsed.as_millis();
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|&bound| ms <= u128::from(bound))
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        stats.buckets[bucket] += 1;
+    }
+
+    /// Counters as JSON: totals, then calls, errors and latency per tool
+    ///
+    /// Histograms are arrays aligned with `latency_buckets_ms`, so they keep
+    /// their order through JSON object key sorting.
+    pub fn to_json(&self) -> serde_json::Value {
+        let tools = self.tools.lock().unwrap_or_else(|e| e.into_inner());
+
+        let per_tool: serde_json::Map<String, serde_json::Value> = tools
+            .iter()
+            .map(|(name, stats)| {
+                let stats = serde_json::json!({
+                    "calls": stats.calls,
+                    "errors": stats.errors,
+                    "avg_ms": average_ms(stats),
+                    "max_ms": stats.max.as_millis() as u64,
+                    "latency_histogram": stats.buckets,
+                });
+                (name.clone(), stats)
+            })
+            .collect();
+
+        serde_json::json!({
+            "uptime_secs": self.started.elapsed().as_secs(),
+            // Calls of at most each bound; `latency_histogram` has one more, slower count
+            "latency_buckets_ms": LATENCY_BUCKETS_MS,
+            "calls": tools.values().map(|s| s.calls).sum::<u64>(),
+            "errors": tools.values().map(|s| s.errors).sum::<u64>(),
+            "tools": per_tool,
+        })
+    }
+
+    /// One line per tool, most called first, for the shutdown log
+    pub fn summary(&self) -> Vec<String> {
+        let tools = self.tools.lock().unwrap_or_else(|e| e.into_inner());
+        let mut tools: Vec<(&String, &ToolStats)> = tools.iter().collect();
+        tools.sort_by(|a, b| b.1.calls.cmp(&a.1.calls).then_with(|| a.0.cmp(b.0)));
+        tools
+            .into_iter()
+            .map(|(name, stats)| {
+                format!(
+                    "{}: {} calls, {} errors, avg {} ms, max {} ms",
+                    name,
+                    stats.calls,
+                    stats.errors,
+                    average_ms(stats),
+                    stats.max.as_millis()
+                )
+            })
+            .collect()
+    }
+}
+
+fn average_ms(stats: &ToolStats) -> u64 {
+    match stats.calls {
+        0 => 0,
+        calls => (stats.total.as_millis() / u128::from(calls)) as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_counts_calls_errors_and_latency() {
+        let metrics = Metrics::default();
+        metrics.record("VisitsSummary_get", false, Duration::from_millis(40));
+        metrics.record("VisitsSummary_get", true, Duration::from_millis(300));
+        metrics.record("Actions_get", false, Duration::from_secs(20));
+
+        let json = metrics.to_json();
+        assert_eq!(json["calls"], 3);
+        assert_eq!(json["errors"], 1);
+
+        let visits = &json["tools"]["VisitsSummary_get"];
+        assert_eq!(visits["calls"], 2);
+        assert_eq!(visits["errors"], 1);
+        assert_eq!(visits["avg_ms"], 170);
+        assert_eq!(visits["max_ms"], 300);
+        assert_eq!(
+            visits["latency_histogram"],
+            serde_json::json!([1, 0, 0, 1, 0, 0, 0, 0, 0])
+        );
+        assert_eq!(json["tools"]["Actions_get"]["latency_histogram"][8], 1);
+
+        assert_eq!(
+            metrics.summary()[0],
+            "VisitsSummary_get: 2 calls, 1 errors, avg 170 ms, max 300 ms"
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! Prompt templates for common analytics questions
+//!
+//! Each prompt expands into step-by-step guidance naming the tools to call,
+//! so users who do not know the Matomo API can still get a useful report.
+
+use super::builtin::LIST_SITES_TOOL;
+use super::{MatomoService, SITE_ID_PARAM};
+use rmcp::model::{
+    GetPromptResult, JsonObject, Prompt, PromptArgument, PromptMessage, PromptMessageRole,
+};
+use rmcp::ErrorData;
+
+/// A prompt and the tools its guidance relies on
+struct PromptTemplate {
+    name: &'static str,
+    description: &'static str,
+    /// Spec tools the prompt calls; it is only listed when they all exist
+    tools: &'static [&'static str],
+    /// Arguments besides `idSite`: (name, description, default)
+    arguments: &'static [(&'static str, &'static str, &'static str)],
+    /// Guidance with `{site}` and `{<argument>}` placeholders
+    template: &'static str,
+}
+
+const PROMPTS: &[PromptTemplate] = &[
+    PromptTemplate {
+        name: "weekly_traffic_summary",
+        description: "Summarize a site's traffic week over week",
+        tools: &["VisitsSummary_get", "Referrers_getReferrerType"],
+        arguments: &[(
+            "date",
+            "Weeks to compare, e.g. last2 (default) or 2024-01-01,2024-01-14",
+            "last2",
+        )],
+        template: "Write a weekly traffic summary for {site}.\n\n\
+                   1. Call VisitsSummary_get with period=week and date={date}: the result is \
+                   keyed by week.\n\
+                   2. Call Referrers_getReferrerType with the same arguments to see where \
+                   visits came from.\n\
+                   3. Report visits, unique visitors, actions per visit, average time on site \
+                   and bounce rate for the latest week, with the change from the previous \
+                   week in percent, then the main traffic channels. Keep it short and point \
+                   out notable changes.",
+    },
+    PromptTemplate {
+        name: "top_referrers",
+        description: "List the websites sending the most visits over a month",
+        tools: &["Referrers_getWebsites"],
+        arguments: &[
+            (
+                "date",
+                "Any day of the month to analyze (default: lastMonth)",
+                "lastMonth",
+            ),
+            ("limit", "Number of referrers to list (default: 10)", "10"),
+        ],
+        template: "List the top {limit} referring websites of {site}.\n\n\
+                   1. Call Referrers_getWebsites with period=month, date={date}, \
+                   filter_limit={limit}, filter_sort_column=nb_visits and \
+                   filter_sort_order=desc.\n\
+                   2. Present a table with the website, visits and share of the month's \
+                   referred visits, and comment on the leading sources.",
+    },
+    PromptTemplate {
+        name: "top_pages",
+        description: "Find the most viewed pages over a period",
+        tools: &["Actions_getPageUrls"],
+        arguments: &[
+            ("period", "day, week, month or year (default: week)", "week"),
+            (
+                "date",
+                "Date in the period (default: yesterday)",
+                "yesterday",
+            ),
+            ("limit", "Number of pages to list (default: 10)", "10"),
+        ],
+        template: "Find the {limit} most viewed pages of {site}.\n\n\
+                   1. Call Actions_getPageUrls with period={period}, date={date}, flat=1, \
+                   filter_limit={limit}, filter_sort_column=nb_hits and \
+                   filter_sort_order=desc.\n\
+                   2. Present a table with the page URL, page views, unique page views and \
+                   average time on page, and highlight pages with a high exit rate.",
+    },
+    PromptTemplate {
+        name: "goal_conversions",
+        description: "Review goal conversions and revenue over a period",
+        tools: &["Goals_get"],
+        arguments: &[
+            (
+                "period",
+                "day, week, month or year (default: month)",
+                "month",
+            ),
+            ("date", "Periods to review (default: last3)", "last3"),
+        ],
+        template: "Review the goal conversions of {site}.\n\n\
+                   1. Call Goals_get with period={period} and date={date}: the result is \
+                   keyed by period.\n\
+                   2. Report conversions, conversion rate and revenue for each period and \
+                   describe the trend.",
+    },
+];
+
+impl MatomoService {
+    /// Prompts whose tools are all exposed by the loaded spec
+    pub(super) fn prompts(&self) -> Vec<Prompt> {
+        self.available_templates()
+            .map(|template| {
+                let mut arguments = vec![PromptArgument {
+                    name: SITE_ID_PARAM.to_string(),
+                    title: None,
+                    description: Some(match self.default_site_id {
+                        Some(ref site_id) => format!("Site to analyze (default: {})", site_id),
+                        None => format!("Site to analyze (see {})", LIST_SITES_TOOL),
+                    }),
+                    required: Some(false),
+                }];
+                arguments.extend(template.arguments.iter().map(|(name, description, _)| {
+                    PromptArgument {
+                        name: name.to_string(),
+                        title: None,
+                        description: Some(description.to_string()),
+                        required: Some(false),
+                    }
+                }));
+                Prompt::new(template.name, Some(template.description), Some(arguments))
+            })
+            .collect()
+    }
+
+    /// Expand a prompt with its arguments
+    pub(super) fn render_prompt(
+        &self,
+        name: &str,
+        arguments: Option<&JsonObject>,
+    ) -> Result<GetPromptResult, ErrorData> {
+        let template = self
+            .available_templates()
+            .find(|template| template.name == name)
+            .ok_or_else(|| ErrorData::invalid_params(format!("Unknown prompt: {}", name), None))?;
+
+        let argument = |key: &str| {
+            arguments
+                .and_then(|args| args.get(key))
+                .and_then(|value| match value {
+                    serde_json::Value::String(s) if !s.trim().is_empty() => {
+                        Some(s.trim().to_string())
+                    }
+                    serde_json::Value::Number(n) => Some(n.to_string()),
+                    _ => None,
+                })
+        };
+
+        let site = match argument(SITE_ID_PARAM).or_else(|| self.default_site_id.clone()) {
+            Some(site_id) => format!("site {} (pass idSite={} to every tool)", site_id, site_id),
+            None => format!(
+                "the site the user asks about (call {} first to find its idSite, and ask \
+                 the user if several sites match)",
+                LIST_SITES_TOOL
+            ),
+        };
+
+        let mut text = template.template.replace("{site}", &site);
+        for (key, _, default) in template.arguments {
+            let value = argument(key).unwrap_or_else(|| default.to_string());
+            text = text.replace(&format!("{{{}}}", key), &value);
+        }
+
+        Ok(GetPromptResult {
+            description: Some(template.description.to_string()),
+            messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+        })
+    }
+
+    fn available_templates(&self) -> impl Iterator<Item = &'static PromptTemplate> + '_ {
+        PROMPTS.iter().filter(|template| {
+            template
+                .tools
+                .iter()
+                .all(|tool| self.find_tool(tool).is_some())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates_only_use_declared_arguments() {
+        for template in PROMPTS {
+            let mut text = template.template.replace("{site}", "");
+            for (key, _, _) in template.arguments {
+                text = text.replace(&format!("{{{}}}", key), "");
+            }
+            assert!(
+                !text.contains('{'),
+                "{} has an unknown placeholder",
+                template.name
+            );
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Post-deploy self-test (`--selftest`)
+//!
+//! Runs a connectivity check and one representative report through the same
+//! path as MCP tool calls (defaults, validation, site access check, retries),
+//! so a passing run means the deployed configuration actually serves data.
+
+use super::builtin::PING_TOOL;
+use super::{MatomoService, SITE_ID_PARAM};
+use rmcp::model::{CallToolResult, JsonObject};
+use serde_json::json;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Outcome of one self-test call
+#[derive(Debug)]
+pub struct Check {
+    pub tool: String,
+    pub passed: bool,
+    pub latency: Duration,
+    /// Matomo version, report summary or error message
+    pub detail: String,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}  {:<20} {:>6} ms  {}",
+            if self.passed { "PASS" } else { "FAIL" },
+            self.tool,
+            self.latency.as_millis(),
+            self.detail
+        )
+    }
+}
+
+impl MatomoService {
+    /// Call `matomo_ping` then yesterday's `VisitsSummary.get`
+    ///
+    /// The report uses the default site, or the first site the token can view.
+    pub async fn selftest(&self) -> Vec<Check> {
+        let mut checks = vec![self.check(PING_TOOL, JsonObject::new()).await];
+
+        let Some(tool) = self
+            .tools
+            .iter()
+            .find(|tool| tool.module == "VisitsSummary" && tool.action == "get")
+        else {
+            checks.push(Check {
+                tool: "VisitsSummary.get".to_string(),
+                passed: false,
+                latency: Duration::ZERO,
+                detail: "not exposed by the loaded spec".to_string(),
+            });
+            return checks;
+        };
+
+        let mut arguments = JsonObject::new();
+        arguments.insert("period".to_string(), json!("day"));
+        arguments.insert("date".to_string(), json!("yesterday"));
+        if self.default_site_id.is_none() {
+            let site_id = match self.client.accessible_site_ids(None).await {
+                Ok(ids) => ids.first().copied(),
+                Err(e) => {
+                    checks.push(Check {
+                        tool: tool.name.clone(),
+                        passed: false,
+                        latency: Duration::ZERO,
+                        detail: format!("cannot list the accessible sites: {:#}", e),
+                    });
+                    return checks;
+                }
+            };
+            arguments.insert(SITE_ID_PARAM.to_string(), json!(site_id.unwrap_or(1)));
+        }
+        checks.push(self.check(&tool.name, arguments).await);
+        checks
+    }
+
+    async fn check(&self, tool: &str, arguments: JsonObject) -> Check {
+        let started = Instant::now();
+        let result = self.run_tool(tool, Some(arguments)).await;
+        let latency = started.elapsed();
+
+        let (passed, detail) = match result {
+            Ok(result) => (result.is_error != Some(true), summary(&result)),
+            Err(e) => (false, e.message.to_string()),
+        };
+        Check {
+            tool: tool.to_string(),
+            passed,
+            latency,
+            detail,
+        }
+    }
+}
+
+/// One-line digest of a tool result
+fn summary(result: &CallToolResult) -> String {
+    let value = result.structured_content.clone().or_else(|| {
+        let text = &result.content.first()?.as_text()?.text;
+        serde_json::from_str(text).ok()
+    });
+    match value {
+        Some(value) if value["error"].is_string() => {
+            value["error"].as_str().unwrap_or_default().to_string()
+        }
+        Some(value) if value.get("version").is_some() => {
+            format!("Matomo {}", value["version"].as_str().unwrap_or("?"))
+        }
+        Some(value) if value.get("nb_visits").is_some() => {
+            format!("{} visits", value["nb_visits"])
+        }
+        Some(_) => "ok".to_string(),
+        None => "non-JSON response".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::Content;
+
+    #[test]
+    fn test_check_line_and_summary() {
+        let result = CallToolResult::success(vec![Content::text(r#"{"nb_visits": 42}"#)]);
+        let check = Check {
+            tool: "VisitsSummary_get".to_string(),
+            passed: true,
+            latency: Duration::from_millis(87),
+            detail: summary(&result),
+        };
+        assert_eq!(
+            check.to_string(),
+            "PASS  VisitsSummary_get        87 ms  42 visits"
+        );
+
+        let ping = CallToolResult::structured_error(json!({
+            "status": "unauthorized",
+            "version": null,
+            "error": "Token rejected",
+        }));
+        assert_eq!(summary(&ping), "Token rejected");
+    }
+}
@@ -0,0 +1,244 @@
+//! Differences between two generated specs
+//!
+//! Run after a Matomo upgrade to see which methods and parameters changed
+//! before downstream consumers pick up the new spec.
+
+use crate::openapi::{MatomoTool, OpenApiSpec, ToolParameter};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Changes to one operation present in both specs
+#[derive(Debug, Default, PartialEq)]
+struct OperationDiff {
+    added_parameters: Vec<String>,
+    removed_parameters: Vec<String>,
+    /// Parameter name, added enum values, removed enum values
+    enum_changes: Vec<(String, Vec<String>, Vec<String>)>,
+}
+
+impl OperationDiff {
+    fn is_empty(&self) -> bool {
+        self.added_parameters.is_empty()
+            && self.removed_parameters.is_empty()
+            && self.enum_changes.is_empty()
+    }
+}
+
+/// Operations added, removed and changed between an old and a new spec
+#[derive(Debug, Default)]
+pub struct SpecDiff {
+    added: Vec<String>,
+    removed: Vec<String>,
+    changed: BTreeMap<String, OperationDiff>,
+}
+
+impl SpecDiff {
+    /// Compare operations by tool name (`Module_action`)
+    pub fn between(old: &OpenApiSpec, new: &OpenApiSpec) -> Self {
+        let old = tools_by_name(old);
+        let new = tools_by_name(new);
+
+        let mut diff = SpecDiff {
+            added: new
+                .keys()
+                .filter(|name| !old.contains_key(*name))
+                .cloned()
+                .collect(),
+            removed: old
+                .keys()
+                .filter(|name| !new.contains_key(*name))
+                .cloned()
+                .collect(),
+            ..Default::default()
+        };
+
+        for (name, old_tool) in &old {
+            if let Some(new_tool) = new.get(name) {
+                let changes = diff_operation(old_tool, new_tool);
+                if !changes.is_empty() {
+                    diff.changed.insert(name.clone(), changes);
+                }
+            }
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for SpecDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+
+        for name in &self.added {
+            writeln!(f, "+ {}", name)?;
+        }
+        for name in &self.removed {
+            writeln!(f, "- {}", name)?;
+        }
+        for (name, changes) in &self.changed {
+            writeln!(f, "~ {}", name)?;
+            for param in &changes.added_parameters {
+                writeln!(f, "    + parameter {}", param)?;
+            }
+            for param in &changes.removed_parameters {
+                writeln!(f, "    - parameter {}", param)?;
+            }
+            for (param, added, removed) in &changes.enum_changes {
+                let values: Vec<String> = added
+                    .iter()
+                    .map(|v| format!("+{}", v))
+                    .chain(removed.iter().map(|v| format!("-{}", v)))
+                    .collect();
+                writeln!(f, "    ~ parameter {} enum: {}", param, values.join(" "))?;
+            }
+        }
+
+        writeln!(
+            f,
+            "\n{} added, {} removed, {} changed",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        )
+    }
+}
+
+/// Tools of a spec, leaving out the operations the server would skip
+fn tools_by_name(spec: &OpenApiSpec) -> BTreeMap<String, MatomoTool> {
+    let mut spec = spec.clone();
+    spec.remove_unusable_operations();
+    spec.extract_tools()
+        .into_iter()
+        .map(|tool| (tool.name.clone(), tool))
+        .collect()
+}
+
+fn diff_operation(old: &MatomoTool, new: &MatomoTool) -> OperationDiff {
+    let params = |tool: &MatomoTool| -> BTreeMap<String, ToolParameter> {
+        tool.parameters
+            .iter()
+            .map(|p| (p.name.clone(), p.clone()))
+            .collect()
+    };
+    let old_params = params(old);
+    let new_params = params(new);
+
+    let mut diff = OperationDiff {
+        added_parameters: new_params
+            .keys()
+            .filter(|name| !old_params.contains_key(*name))
+            .cloned()
+            .collect(),
+        removed_parameters: old_params
+            .keys()
+            .filter(|name| !new_params.contains_key(*name))
+            .cloned()
+            .collect(),
+        ..Default::default()
+    };
+
+    for (name, old_param) in &old_params {
+        let Some(new_param) = new_params.get(name) else {
+            continue;
+        };
+        let values = |param: &ToolParameter| -> BTreeSet<String> {
+            param.enum_values.iter().flatten().cloned().collect()
+        };
+        let (old_values, new_values) = (values(old_param), values(new_param));
+        if old_values != new_values {
+            diff.enum_changes.push((
+                name.clone(),
+                new_values.difference(&old_values).cloned().collect(),
+                old_values.difference(&new_values).cloned().collect(),
+            ));
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(paths: serde_json::Value) -> OpenApiSpec {
+        serde_json::from_value(serde_json::json!({
+            "openapi": "3.0.3",
+            "info": {"title": "Matomo", "version": "5.0.0"},
+            "servers": [{"url": "https://matomo.example.com"}],
+            "paths": paths
+        }))
+        .unwrap()
+    }
+
+    fn operation(method: &str, params: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({"get": {
+            "operationId": method.replace('.', "_"),
+            "x-matomo-method": method,
+            "parameters": params,
+            "responses": {"200": {"description": "OK"}}
+        }})
+    }
+
+    #[test]
+    fn test_reports_operation_and_parameter_changes() {
+        let old = spec(serde_json::json!({
+            "/index.php/VisitsSummary/get": operation("VisitsSummary.get", serde_json::json!([
+                {"name": "period", "in": "query", "required": true,
+                 "schema": {"type": "string", "enum": ["day", "week"]}},
+                {"name": "segment", "in": "query", "required": false,
+                 "schema": {"type": "string"}}
+            ])),
+            "/index.php/Actions/get": operation("Actions.get", serde_json::json!([]))
+        }));
+        let new = spec(serde_json::json!({
+            "/index.php/VisitsSummary/get": operation("VisitsSummary.get", serde_json::json!([
+                {"name": "period", "in": "query", "required": true,
+                 "schema": {"type": "string", "enum": ["day", "range"]}},
+                {"name": "columns", "in": "query", "required": false,
+                 "schema": {"type": "string"}}
+            ])),
+            "/index.php/Goals/get": operation("Goals.get", serde_json::json!([]))
+        }));
+
+        let diff = SpecDiff::between(&old, &new);
+        assert_eq!(diff.added, vec!["Goals_get"]);
+        assert_eq!(diff.removed, vec!["Actions_get"]);
+        assert_eq!(
+            diff.changed["VisitsSummary_get"],
+            OperationDiff {
+                added_parameters: vec!["columns".to_string()],
+                removed_parameters: vec!["segment".to_string()],
+                enum_changes: vec![(
+                    "period".to_string(),
+                    vec!["range".to_string()],
+                    vec!["week".to_string()]
+                )],
+            }
+        );
+        assert!(SpecDiff::between(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_unusable_operations_are_left_out() {
+        let old = spec(serde_json::json!({
+            "/index.php/Actions/get": operation("Actions.get", serde_json::json!([
+                {"name": "segment", "in": "query", "schema": {"type": "string"}}
+            ]))
+        }));
+        let long_method = format!("Actions.{}", "a".repeat(64));
+        let mut new = old.clone();
+        new.paths.insert(
+            "/index.php/Actions/long".to_string(),
+            serde_json::from_value(operation(&long_method, serde_json::json!([]))).unwrap(),
+        );
+
+        assert!(SpecDiff::between(&old, &new).is_empty());
+    }
+}
@@ -0,0 +1,146 @@
+//! Loading of the API token from a file or stdin
+//!
+//! Rotating tokens written by a sidecar can be re-read periodically, so the
+//! running server picks up the new value without a restart.
+
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::matomo_client::MatomoClient;
+
+/// `--token` value reading the token from stdin
+pub const STDIN_TOKEN: &str = "-";
+
+/// Token from `--token-file` or `--token` (`-` reads it from stdin)
+///
+/// Setting both is an error rather than a silent pick, since one of them
+/// may come from a forgotten environment variable.
+pub fn resolve_token(token: Option<&str>, token_file: Option<&Path>) -> Result<Option<String>> {
+    match (token, token_file) {
+        (Some(_), Some(_)) => anyhow::bail!(
+            "The API token is set twice: by --token (or MCP_MATOMO_TOKEN) and by --token-file \
+             (or MCP_MATOMO_TOKEN_FILE); unset one. The token comes from exactly one of them \
+             (--token - reads stdin); only when neither is set is the token of the --config \
+             default instance used"
+        ),
+        (None, Some(path)) => read_token_file(path).map(Some),
+        (Some(STDIN_TOKEN), None) => read_token_stdin().map(Some),
+        (Some(token), None) => Ok(Some(token.to_string())),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Read the token from the first line of stdin
+///
+/// Reads byte by byte from an unbuffered handle: whatever follows the line
+/// stays in stdin for the MCP stdio transport.
+fn read_token_stdin() -> Result<String> {
+    let stdin = unbuffered_stdin().context("Failed to open stdin")?;
+    let token = read_line(stdin).context("Failed to read the token from stdin")?;
+    if token.is_empty() {
+        anyhow::bail!("No token on stdin (--token -)");
+    }
+    Ok(token)
+}
+
+#[cfg(unix)]
+fn unbuffered_stdin() -> std::io::Result<std::fs::File> {
+    use std::os::fd::AsFd;
+    Ok(std::io::stdin().as_fd().try_clone_to_owned()?.into())
+}
+
+#[cfg(windows)]
+fn unbuffered_stdin() -> std::io::Result<std::fs::File> {
+    use std::os::windows::io::AsHandle;
+    Ok(std::io::stdin().as_handle().try_clone_to_owned()?.into())
+}
+
+/// First line of `reader` without surrounding whitespace
+fn read_line(mut reader: impl Read) -> std::io::Result<String> {
+    let mut line = Vec::new();
+    let mut byte = [0u8];
+    while reader.read(&mut byte)? == 1 && byte[0] != b'\n' {
+        line.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&line).trim().to_string())
+}
+
+/// Read a token from a file, ignoring surrounding whitespace
+pub fn read_token_file(path: &Path) -> Result<String> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read token file {:?}", path))?;
+    let token = content.trim();
+    if token.is_empty() {
+        anyhow::bail!("Token file {:?} is empty", path);
+    }
+    Ok(token.to_string())
+}
+
+/// Re-read the token file every `interval` and update the client's token
+///
+/// Unchanged content is a no-op. Read errors keep the current token.
+pub fn spawn_token_reload(
+    client: Arc<MatomoClient>,
+    path: PathBuf,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick completes immediately; the token was just read
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+            match read_token_file(&path) {
+                Ok(token) => {
+                    if client.set_token(Some(token)) {
+                        info!("API token reloaded from {:?}", path);
+                    }
+                }
+                Err(e) => warn!("Keeping current API token: {:#}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_token_file_trims_whitespace() {
+        let path = std::env::temp_dir().join(format!("mcp-matomo-token-{}", std::process::id()));
+
+        std::fs::write(&path, "  abc123\n").unwrap();
+        assert_eq!(read_token_file(&path).unwrap(), "abc123");
+
+        std::fs::write(&path, "\n").unwrap();
+        assert!(read_token_file(&path).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_line_stops_at_newline() {
+        let mut input: &[u8] = b"abc123\r\n{\"jsonrpc\":\"2.0\"}\n";
+        assert_eq!(read_line(&mut input).unwrap(), "abc123");
+        assert_eq!(input, b"{\"jsonrpc\":\"2.0\"}\n");
+    }
+
+    #[test]
+    fn test_resolve_token_rejects_two_sources() {
+        assert_eq!(
+            resolve_token(Some("abc"), None).unwrap().as_deref(),
+            Some("abc")
+        );
+        assert!(resolve_token(None, None).unwrap().is_none());
+        let err = resolve_token(Some("abc"), Some(Path::new("/tmp/token")))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("MCP_MATOMO_TOKEN_FILE"), "{}", err);
+    }
+}
@@ -0,0 +1,134 @@
+//! Truncation of oversized tool responses
+//!
+//! Some reports (e.g. `Actions.getPageUrls` with `expanded=1`) return
+//! megabytes of JSON. Responses over the configured size are cut on row
+//! boundaries so the result stays valid JSON, and a note tells the model how
+//! much was left out.
+
+/// A response cut down to fit the size limit
+pub struct Truncated {
+    pub value: serde_json::Value,
+    pub note: String,
+}
+
+/// Size of a value once rendered as tool output
+fn rendered_len(value: &serde_json::Value) -> usize {
+    match value {
+        serde_json::Value::String(s) => s.len(),
+        _ => serde_json::to_string_pretty(value)
+            .map(|s| s.len())
+            .unwrap_or(0),
+    }
+}
+
+/// Size of a value rendered inside an array or object, one level deeper
+fn nested_len(value: &serde_json::Value) -> usize {
+    let rendered = serde_json::to_string_pretty(value).unwrap_or_default();
+    // Two spaces of extra indentation per line, plus the ",\n" separator
+    rendered.len() + 2 * rendered.lines().count() + 2
+}
+
+/// Truncate a response rendering to more than `max_bytes`
+///
+/// Arrays keep their leading rows, objects their leading entries and raw
+/// strings (CSV, TSV, ...) their leading lines. Returns `None` when the
+/// response fits or cannot be cut (scalars).
+pub fn truncate_response(value: &serde_json::Value, max_bytes: usize) -> Option<Truncated> {
+    let total_bytes = rendered_len(value);
+    if total_bytes <= max_bytes {
+        return None;
+    }
+
+    let (value, kept, total, unit) = match value {
+        serde_json::Value::Array(rows) => {
+            let kept = count_fitting(rows.iter().map(nested_len), max_bytes);
+            let truncated = serde_json::Value::Array(rows[..kept].to_vec());
+            (truncated, kept, rows.len(), "rows")
+        }
+        serde_json::Value::Object(obj) => {
+            let kept = count_fitting(
+                obj.iter().map(|(key, val)| key.len() + 4 + nested_len(val)),
+                max_bytes,
+            );
+            let truncated = obj
+                .iter()
+                .take(kept)
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            (
+                serde_json::Value::Object(truncated),
+                kept,
+                obj.len(),
+                "entries",
+            )
+        }
+        serde_json::Value::String(text) => {
+            let lines: Vec<&str> = text.lines().collect();
+            let kept = count_fitting(lines.iter().map(|l| l.len() + 1), max_bytes);
+            let truncated = serde_json::Value::String(lines[..kept].join("\n"));
+            (truncated, kept, lines.len(), "lines")
+        }
+        _ => return None,
+    };
+
+    Some(Truncated {
+        value,
+        note: format!(
+            "[Response truncated: showing {} of {} {} ({} bytes in total). \
+             Use filter_limit (and filter_offset) to request fewer rows.]",
+            kept, total, unit, total_bytes
+        ),
+    })
+}
+
+/// Number of leading items whose sizes fit in `max_bytes`
+fn count_fitting(sizes: impl Iterator<Item = usize>, max_bytes: usize) -> usize {
+    // Opening and closing brackets
+    let mut used = 4;
+    let mut kept = 0;
+    for size in sizes {
+        used += size;
+        if used > max_bytes {
+            break;
+        }
+        kept += 1;
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_small_response_is_untouched() {
+        assert!(truncate_response(&json!([{"label": "a"}]), 1000).is_none());
+    }
+
+    #[test]
+    fn test_array_is_cut_on_row_boundaries() {
+        let rows: Vec<serde_json::Value> = (0..100)
+            .map(|i| json!({"label": format!("/page-{}", i), "nb_hits": i}))
+            .collect();
+        let truncated = truncate_response(&json!(rows), 500).unwrap();
+
+        let kept = truncated.value.as_array().unwrap();
+        assert!(!kept.is_empty() && kept.len() < 100);
+        assert_eq!(kept[0], rows[0]);
+        assert!(rendered_len(&truncated.value) <= 500);
+        assert!(truncated.note.contains("of 100 rows"), "{}", truncated.note);
+    }
+
+    #[test]
+    fn test_csv_is_cut_on_line_boundaries() {
+        let csv = (0..50)
+            .map(|i| format!("label{},{}", i, i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let truncated = truncate_response(&json!(csv), 100).unwrap();
+        let text = truncated.value.as_str().unwrap();
+        assert!(text.starts_with("label0,0\nlabel1,1"));
+        assert!(text.lines().all(|l| l.starts_with("label")));
+    }
+}
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 /// Represents a Matomo API method with its metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +20,18 @@ pub struct MatomoMethod {
     pub description: Option<String>,
     /// Method category
     pub category: Option<String>,
+    /// Display order within the category
+    #[serde(default)]
+    pub order: Option<i64>,
+    /// Unit of each metric returned by the method (e.g. "seconds")
+    #[serde(default)]
+    pub metric_units: BTreeMap<String, String>,
+    /// Label of each metric returned by the method (e.g. "Visits")
+    #[serde(default)]
+    pub metrics: BTreeMap<String, String>,
+    /// Whether Matomo marks the method as deprecated
+    #[serde(default)]
+    pub deprecated: bool,
 }
 
 /// Represents a parameter for a Matomo API method
@@ -30,6 +42,12 @@ pub struct MatomoParameter {
     pub param_type: ParameterType,
     pub default_value: Option<String>,
     pub description: Option<String>,
+    /// Allowed values discovered during introspection (overrides the built-in enums)
+    #[serde(default)]
+    pub enum_values: Option<Vec<String>>,
+    /// Whether the parameter is deprecated
+    #[serde(default)]
+    pub deprecated: bool,
 }
 
 /// Possible parameter types in Matomo API
@@ -71,7 +89,7 @@ pub struct JsonSchema {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub items: Option<Box<JsonSchema>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub properties: Option<HashMap<String, JsonSchema>>,
+    pub properties: Option<BTreeMap<String, JsonSchema>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub additional_properties: Option<Box<JsonSchema>>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -112,6 +130,9 @@ impl Default for JsonSchema {
 pub struct MethodMetadata {
     pub parameters: Vec<MethodParameter>,
     pub example_url: Option<String>,
+    /// Whether the reference flags the method as deprecated
+    #[serde(default)]
+    pub deprecated: bool,
 }
 
 /// Parameter from the documentation
@@ -120,4 +141,10 @@ pub struct MethodParameter {
     pub name: String,
     pub required: bool,
     pub default: Option<String>,
+    /// Values observed for this parameter in the report metadata, sorted
+    #[serde(default)]
+    pub known_values: Vec<String>,
+    /// Whether the signature names the parameter as deprecated
+    #[serde(default)]
+    pub deprecated: bool,
 }
@@ -0,0 +1,445 @@
+//! Validation of tool arguments against the tool's parameter list
+//!
+//! Catches misspelled, missing or wrongly typed arguments before they reach
+//! Matomo, which would otherwise answer with a confusing error.
+
+use std::collections::HashMap;
+
+use crate::dates::{check_date_range, is_multi_date, normalize_date, period_synonym, DATE_FORMATS};
+use crate::openapi::{MatomoTool, ToolParameter};
+
+/// Pattern of the string forms of `idSite`: `all` or a list of ids such as `1,3`
+pub const SITE_LIST_PATTERN: &str = r"^\s*(all|\d+(\s*,\s*\d+)*)\s*$";
+
+/// Validate and coerce arguments in place
+///
+/// Rejects unknown parameter names and missing required parameters, enforces
+/// enum values (case-insensitively, normalizing to the canonical spelling) and
+/// numeric bounds, and coerces numeric strings for integer/number parameters
+/// and textual booleans ("true", "no", ...) to Matomo's "1" / "0". Returns a
+/// note for each `date` / `period` value that was corrected.
+pub fn validate_arguments(
+    tool: &MatomoTool,
+    params: &mut HashMap<String, serde_json::Value>,
+) -> Result<Vec<String>, String> {
+    for name in params.keys() {
+        if !tool.parameters.iter().any(|p| &p.name == name) {
+            let mut known: Vec<&str> = tool.parameters.iter().map(|p| p.name.as_str()).collect();
+            known.sort();
+            return Err(format!(
+                "Unknown parameter '{}' for {}. Allowed parameters: {}",
+                name,
+                tool.name,
+                known.join(", ")
+            ));
+        }
+    }
+
+    let corrections = check_date_period(params)?;
+
+    for param in &tool.parameters {
+        match params.get_mut(&param.name) {
+            None | Some(serde_json::Value::Null) => {
+                if param.required {
+                    return Err(format!(
+                        "Missing required parameter '{}' for {}",
+                        param.name, tool.name
+                    ));
+                }
+            }
+            Some(value) => {
+                coerce_type(param, value)?;
+                check_enum(param, value)?;
+                check_range(param, value)?;
+            }
+        }
+    }
+
+    Ok(corrections)
+}
+
+/// Correct and check the `date` / `period` pair, returning correction notes
+fn check_date_period(
+    params: &mut HashMap<String, serde_json::Value>,
+) -> Result<Vec<String>, String> {
+    let mut corrections = Vec::new();
+
+    if let Some(serde_json::Value::String(period)) = params.get_mut("period") {
+        if let Some(canonical) = period_synonym(period) {
+            corrections.push(format!(
+                "period '{}' was corrected to '{}'",
+                period, canonical
+            ));
+            *period = canonical.to_string();
+        }
+    }
+
+    if let Some(serde_json::Value::String(date)) = params.get_mut("date") {
+        let normalized = normalize_date(date)?;
+        if normalized != *date {
+            corrections.push(format!("date '{}' was corrected to '{}'", date, normalized));
+            *date = normalized;
+        }
+    }
+
+    let period = params.get("period").and_then(|v| v.as_str());
+    let date = params.get("date").and_then(|v| v.as_str());
+    if let (Some("range"), Some(date)) = (period, date) {
+        if !is_multi_date(date) {
+            return Err(format!(
+                "period=range needs a date range, got '{}'. Accepted formats: {}",
+                date, DATE_FORMATS
+            ));
+        }
+        check_date_range(date)?;
+    }
+
+    Ok(corrections)
+}
+
+/// `all`, or a comma-separated list of site ids normalized to `1,3`
+fn site_list(s: &str) -> Option<String> {
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("all") {
+        return Some("all".to_string());
+    }
+    if !s.contains(',') {
+        return None;
+    }
+    s.split(',')
+        .map(|id| id.trim().parse::<u64>().ok().map(|id| id.to_string()))
+        .collect::<Option<Vec<_>>>()
+        .map(|ids| ids.join(","))
+}
+
+/// Verify a value against the parameter type, coercing where unambiguous
+fn coerce_type(param: &ToolParameter, value: &mut serde_json::Value) -> Result<(), String> {
+    let type_error = |value: &serde_json::Value| {
+        Err(format!(
+            "Parameter '{}' must be of type {}, got {}",
+            param.name, param.param_type, value
+        ))
+    };
+
+    // Multi-site reports take `all` or a list of ids besides a single id
+    if param.name == "idSite" {
+        if let Some(sites) = value.as_str().and_then(site_list) {
+            *value = sites.into();
+            return Ok(());
+        }
+    }
+
+    match param.param_type.as_str() {
+        "integer" => match value {
+            serde_json::Value::Number(n) if n.is_i64() || n.is_u64() => Ok(()),
+            serde_json::Value::String(s) => match s.trim().parse::<i64>() {
+                Ok(n) => {
+                    *value = n.into();
+                    Ok(())
+                }
+                Err(_) => type_error(value),
+            },
+            _ => type_error(value),
+        },
+        "number" => match value {
+            serde_json::Value::Number(_) => Ok(()),
+            serde_json::Value::String(s) => match s
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+            {
+                Some(n) => {
+                    *value = serde_json::Value::Number(n);
+                    Ok(())
+                }
+                None => type_error(value),
+            },
+            _ => type_error(value),
+        },
+        "boolean" => match value {
+            serde_json::Value::Bool(_) => Ok(()),
+            serde_json::Value::Number(n) if n.as_u64() == Some(0) || n.as_u64() == Some(1) => {
+                Ok(())
+            }
+            // Matomo reads any non-empty string such as "false" as true
+            serde_json::Value::String(s) => match boolean_flag(s) {
+                Some(flag) => {
+                    *value = serde_json::Value::String(flag.to_string());
+                    Ok(())
+                }
+                None => type_error(value),
+            },
+            _ => type_error(value),
+        },
+        // Lists are sent comma-separated: accept arrays of scalars or a joined string
+        "array" => match value {
+            serde_json::Value::Array(items) if items.iter().all(is_scalar) => Ok(()),
+            serde_json::Value::String(_) | serde_json::Value::Number(_) => Ok(()),
+            _ => type_error(value),
+        },
+        "string" => match value {
+            serde_json::Value::String(_)
+            | serde_json::Value::Number(_)
+            | serde_json::Value::Bool(_) => Ok(()),
+            _ => type_error(value),
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Matomo's "1" / "0" for the usual textual spellings of a boolean
+fn boolean_flag(s: &str) -> Option<&'static str> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" => Some("1"),
+        "false" | "0" | "no" | "" => Some("0"),
+        _ => None,
+    }
+}
+
+/// Whether a value can be sent as a single form value
+fn is_scalar(value: &serde_json::Value) -> bool {
+    matches!(
+        value,
+        serde_json::Value::String(_) | serde_json::Value::Number(_) | serde_json::Value::Bool(_)
+    )
+}
+
+/// Reject numbers outside the parameter's `minimum` / `maximum`
+fn check_range(param: &ToolParameter, value: &serde_json::Value) -> Result<(), String> {
+    let Some(number) = value.as_f64() else {
+        return Ok(());
+    };
+    let below = param.minimum.filter(|&min| number < min as f64);
+    let above = param.maximum.filter(|&max| number > max as f64);
+    match (below, above) {
+        (Some(min), _) => Err(format!(
+            "Parameter '{}' must be at least {}, got {}",
+            param.name, min, value
+        )),
+        (_, Some(max)) => Err(format!(
+            "Parameter '{}' must be at most {}, got {}",
+            param.name, max, value
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Enforce enum values, normalizing case to the canonical value
+fn check_enum(param: &ToolParameter, value: &mut serde_json::Value) -> Result<(), String> {
+    let Some(ref allowed) = param.enum_values else {
+        return Ok(());
+    };
+
+    let given = match &*value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+
+    match allowed.iter().find(|a| a.eq_ignore_ascii_case(&given)) {
+        Some(canonical) => {
+            if value.is_string() {
+                *value = serde_json::Value::String(canonical.clone());
+            }
+            Ok(())
+        }
+        None => Err(format!(
+            "Invalid value '{}' for parameter '{}'. Allowed values: {}",
+            given,
+            param.name,
+            allowed.join(", ")
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn param(name: &str, param_type: &str, required: bool) -> ToolParameter {
+        ToolParameter {
+            name: name.to_string(),
+            description: None,
+            required,
+            param_type: param_type.to_string(),
+            default: None,
+            enum_values: None,
+            item_type: None,
+            example: None,
+            minimum: None,
+            maximum: None,
+            deprecated: false,
+        }
+    }
+
+    fn tool() -> MatomoTool {
+        let mut period = param("period", "string", false);
+        period.enum_values = Some(vec![
+            "day".to_string(),
+            "week".to_string(),
+            "range".to_string(),
+        ]);
+        let mut filter_limit = param("filter_limit", "integer", false);
+        filter_limit.minimum = Some(-1);
+
+        MatomoTool {
+            name: "VisitsSummary_get".to_string(),
+            module: "VisitsSummary".to_string(),
+            action: "get".to_string(),
+            description: String::new(),
+            parameters: vec![
+                param("idSite", "integer", true),
+                period,
+                param("filter_excludelowpop_value", "number", false),
+                param("flat", "boolean", false),
+                param("date", "string", false),
+                filter_limit,
+            ],
+            metric_units: Default::default(),
+            response_schema: None,
+            deprecated: false,
+        }
+    }
+
+    fn args(value: serde_json::Value) -> HashMap<String, serde_json::Value> {
+        value
+            .as_object()
+            .unwrap()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_arguments() {
+        let mut params = args(json!({"idSite": 1, "period": "day", "flat": true}));
+        assert!(validate_arguments(&tool(), &mut params).is_ok());
+    }
+
+    #[test]
+    fn test_unknown_parameter() {
+        let mut params = args(json!({"idSite": 1, "perod": "day"}));
+        let err = validate_arguments(&tool(), &mut params).unwrap_err();
+        assert!(err.contains("'perod'"), "{}", err);
+        assert!(err.contains("period"), "{}", err);
+    }
+
+    #[test]
+    fn test_missing_required_parameter() {
+        let mut params = args(json!({"period": "day"}));
+        let err = validate_arguments(&tool(), &mut params).unwrap_err();
+        assert!(err.contains("'idSite'"), "{}", err);
+    }
+
+    #[test]
+    fn test_enum_violation_lists_allowed_values() {
+        let mut params = args(json!({"idSite": 1, "period": "hourly"}));
+        let err = validate_arguments(&tool(), &mut params).unwrap_err();
+        assert!(err.contains("day, week"), "{}", err);
+    }
+
+    #[test]
+    fn test_enum_case_is_normalized() {
+        let mut params = args(json!({"idSite": 1, "period": "Week"}));
+        validate_arguments(&tool(), &mut params).unwrap();
+        assert_eq!(params["period"], "week");
+    }
+
+    #[test]
+    fn test_numeric_strings_are_coerced() {
+        let mut params = args(json!({"idSite": "3", "filter_excludelowpop_value": "2.5"}));
+        validate_arguments(&tool(), &mut params).unwrap();
+        assert_eq!(params["idSite"], 3);
+        assert_eq!(params["filter_excludelowpop_value"], 2.5);
+    }
+
+    #[test]
+    fn test_multi_site_ids_are_accepted() {
+        for (given, expected) in [
+            ("all", json!("all")),
+            ("1, 3", json!("1,3")),
+            ("7", json!(7)),
+        ] {
+            let mut params = args(json!({ "idSite": given }));
+            validate_arguments(&tool(), &mut params).unwrap();
+            assert_eq!(params["idSite"], expected);
+        }
+
+        let mut params = args(json!({"idSite": "1,x"}));
+        assert!(validate_arguments(&tool(), &mut params).is_err());
+    }
+
+    #[test]
+    fn test_textual_booleans_are_coerced() {
+        for (given, expected) in [
+            ("true", "1"),
+            ("TRUE", "1"),
+            ("1", "1"),
+            ("yes", "1"),
+            ("false", "0"),
+            ("False", "0"),
+            ("0", "0"),
+            ("no", "0"),
+            ("", "0"),
+        ] {
+            let mut params = args(json!({"idSite": 1, "flat": given}));
+            validate_arguments(&tool(), &mut params).unwrap();
+            assert_eq!(params["flat"], expected, "flat={:?}", given);
+        }
+
+        let mut params = args(json!({"idSite": 1, "flat": "maybe"}));
+        let err = validate_arguments(&tool(), &mut params).unwrap_err();
+        assert!(err.contains("boolean"), "{}", err);
+    }
+
+    #[test]
+    fn test_date_and_period_are_corrected() {
+        let mut params = args(json!({"idSite": 1, "period": "weekly", "date": "2024/01/31"}));
+        let corrections = validate_arguments(&tool(), &mut params).unwrap();
+        assert_eq!(params["period"], "week");
+        assert_eq!(params["date"], "2024-01-31");
+        assert_eq!(corrections.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_date_lists_formats() {
+        let mut params = args(json!({"idSite": 1, "date": "31/01/2024"}));
+        let err = validate_arguments(&tool(), &mut params).unwrap_err();
+        assert!(err.contains("lastN"), "{}", err);
+    }
+
+    #[test]
+    fn test_explicit_range_must_be_in_order() {
+        let mut params =
+            args(json!({"idSite": 1, "period": "range", "date": "2024-01-01,2024-01-31"}));
+        assert!(validate_arguments(&tool(), &mut params).is_ok());
+
+        let mut params =
+            args(json!({"idSite": 1, "period": "range", "date": "2024/01/31,2024/01/01"}));
+        let err = validate_arguments(&tool(), &mut params).unwrap_err();
+        assert!(
+            err.contains("start date comes after the end date"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_value_is_rejected() {
+        let mut params = args(json!({"idSite": 1, "filter_limit": "-1"}));
+        assert!(validate_arguments(&tool(), &mut params).is_ok());
+
+        let mut params = args(json!({"idSite": 1, "filter_limit": -5}));
+        let err = validate_arguments(&tool(), &mut params).unwrap_err();
+        assert_eq!(err, "Parameter 'filter_limit' must be at least -1, got -5");
+    }
+
+    #[test]
+    fn test_wrong_type_is_rejected() {
+        let mut params = args(json!({"idSite": "abc"}));
+        let err = validate_arguments(&tool(), &mut params).unwrap_err();
+        assert!(err.contains("'idSite'"), "{}", err);
+        assert!(err.contains("integer"), "{}", err);
+    }
+}